@@ -1,6 +1,17 @@
 use crate::app::endpoint;
+use crate::app::hooks::LifecycleHooks;
+use crate::app::pipeline::{LaunchEvent, LaunchPipeline, WaitingLaunch};
+use crate::app::processes::{CommandOutput, ProcessRegistry, ProcessState};
+use crate::app::scheduler::Scheduler;
+use crate::app::supervisor::{SupervisionState, SupervisionStatus, Supervisor};
+use crate::clipboard::ClipboardProvider;
+use crate::history::{self, HistoryRecord, LaunchSource};
+use crate::model::commands::CommandSpec;
+use crate::model::commands::OutputMode;
+use crate::notify::{self, Notification, Notifier};
+use crate::runner;
 use log::{info, warn};
-use std::sync::mpsc;
+use std::sync::{Arc, mpsc};
 use windows_sys::Win32::Foundation::{GetLastError, HWND};
 // use windows_sys::Win32::UI::WindowsAndMessaging::{
 //     IsWindowVisible, PostMessageW, SW_HIDE, SW_SHOW, SetForegroundWindow, ShowWindow, WM_CLOSE,
@@ -16,6 +27,8 @@ struct ControllerState {
     hwnd: Option<HWND>,
     hotkey_registered: bool,
     ui_thread_id: Option<u32>,
+    // ランチャーを最後に開いた経路（UI からの起動の履歴に残す）
+    shown_by: LaunchSource,
 }
 impl ControllerState {
     pub fn new() -> Self {
@@ -23,6 +36,7 @@ impl ControllerState {
             hwnd: None,
             hotkey_registered: false,
             ui_thread_id: None,
+            shown_by: LaunchSource::Ui,
         }
     }
 }
@@ -33,14 +47,33 @@ pub struct Controller {
     hotkey: endpoint::HotkeyHandle,
     tray: endpoint::TrayHandle,
     finish_rx: mpsc::Receiver<()>,
+    processes: ProcessRegistry,
+    notifier: Box<dyn Notifier>,
+    clipboard: Arc<dyn ClipboardProvider>,
+    scheduler: Scheduler,
+    hooks: LifecycleHooks,
+    supervisor: Supervisor,
+    // 最後に UI とタスクトレイに伝えた起動し直しの状態
+    last_supervision: Vec<SupervisionStatus>,
+    // UI からの起動の重複判定、再起動待ち、同時実行数による待機
+    launches: LaunchPipeline,
+    // 最後に UI に伝えた待機中の起動
+    last_waiting: Vec<WaitingLaunch>,
 }
 
 impl Controller {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         ui: endpoint::UiHandle,
         hotkey: endpoint::HotkeyHandle,
         tray: endpoint::TrayHandle,
         finish_rx: mpsc::Receiver<()>,
+        notifier: Box<dyn Notifier>,
+        clipboard: Arc<dyn ClipboardProvider>,
+        scheduler: Scheduler,
+        hooks: LifecycleHooks,
+        supervisor: Supervisor,
+        launches: LaunchPipeline,
     ) -> Self {
         Self {
             state: ControllerState::new(),
@@ -48,6 +81,15 @@ impl Controller {
             hotkey,
             tray,
             finish_rx,
+            processes: ProcessRegistry::new(),
+            notifier,
+            clipboard,
+            scheduler,
+            hooks,
+            supervisor,
+            last_supervision: Vec::new(),
+            launches,
+            last_waiting: Vec::new(),
         }
     }
 
@@ -56,6 +98,10 @@ impl Controller {
             self.recv_hotkey();
             self.recv_tasktray();
             self.recv_ui();
+            self.reap_processes();
+            self.run_schedule();
+            self.run_supervision();
+            self.run_launches();
             // 終了処理
             if self.finish_rx.try_recv().is_ok() {
                 // // ドロップトレイトからの処理だとうまくいかないのでここで明示的に終了処理を行う
@@ -75,6 +121,7 @@ impl Controller {
             match event {
                 endpoint::HotkeyEvent::Toggle => {
                     // ウィンドウの表示/非表示切り替え
+                    self.state.shown_by = LaunchSource::Hotkey;
                     self.toggle_window();
                 }
                 endpoint::HotkeyEvent::RegisterResult(success) => {
@@ -95,15 +142,26 @@ impl Controller {
             match event {
                 endpoint::TrayEvent::ShowWindow => {
                     info!("タスクトレイから表示イベント受信");
+                    self.state.shown_by = LaunchSource::Tray;
                     // ウィンドウの表示/非表示切り替え
                     self.request_show_window();
                 }
                 endpoint::TrayEvent::Quit => {
                     info!("タスクトレイから終了イベント受信");
+                    // 終了時のコマンドを順に実行し、終わるまで待つ
+                    for record in self.hooks.run_exit() {
+                        history::append_or_warn(&record);
+                    }
                     // 終了処理
                     // UIに終了を伝達
                     self.request_ui_exit();
                 }
+                endpoint::TrayEvent::TerminateProcess(pid) => {
+                    self.terminate_process(pid);
+                }
+                endpoint::TrayEvent::KillProcess(pid) => {
+                    self.kill_process(pid);
+                }
             }
         }
     }
@@ -117,6 +175,10 @@ impl Controller {
 
                     // hotkey に HWND を伝達
                     let _ = self.hotkey.tx.send(endpoint::HotkeyCmd::Register(hwnd));
+
+                    // 起動時のコマンド（UI の準備ができてから 1 回だけ）
+                    let commands = self.hooks.run_start();
+                    self.launch_all(commands, LaunchSource::Hook);
                     // tasktray に HWND を伝達
                     // let _ = self.tray.tx.send(endpoint::TrayCmd::Register(hwnd));
                 }
@@ -130,8 +192,213 @@ impl Controller {
                     info!("UIが非アクティブ化されました。ウィンドウを非表示にします");
                     self.request_hide_window();
                 }
+                endpoint::UiEvent::Launch(command) => {
+                    // 判定は一覧を直接見て行うため、UI への一覧の伝達を待たずに重複を防げる
+                    let events = self.launches.request(
+                        *command,
+                        self.state.shown_by,
+                        &self.processes.snapshot(),
+                    );
+                    self.handle_launch_events(events);
+                }
+                endpoint::UiEvent::CancelWaiting(id) => {
+                    self.launches.cancel(id);
+                }
+                endpoint::UiEvent::TerminateProcess(pid) => {
+                    self.terminate_process(pid);
+                }
+                endpoint::UiEvent::KillProcess(pid) => {
+                    self.kill_process(pid);
+                }
+            }
+        }
+    }
+
+    // 起動したプロセスの終了を回収し、変化があれば UI とタスクトレイに伝達する
+    fn reap_processes(&mut self) {
+        self.processes.enforce_timeouts();
+        let finished = self.processes.reap();
+        if finished.is_empty() {
+            return;
+        }
+        for process in finished {
+            history::append_or_warn(&HistoryRecord::from_process(&process.info));
+            self.supervisor.exited(&process.info);
+            if let Some(notification) = notify::process_exited(&process.info) {
+                self.notify(notification);
+            }
+            if let Some(output) = process.output {
+                self.deliver_output(output);
+            }
+            let duration = process
+                .info
+                .runtime()
+                .and_then(|d| d.to_std().ok())
+                .unwrap_or_default();
+            let exit_code = match process.info.state {
+                ProcessState::Exited(code) => code,
+                ProcessState::Running => None,
+            };
+            self.hooks.run_after(process.after, exit_code, duration);
+        }
+        self.broadcast_processes();
+    }
+    // 定期実行の予定時刻を過ぎたコマンドを起動する
+    fn run_schedule(&mut self) {
+        let snapshot = self.processes.snapshot();
+        let fired = self.scheduler.tick(|name| {
+            snapshot.iter().any(|p| p.is_running() && p.name == name)
+                || self.launches.is_in_flight(name)
+        });
+        self.launch_all(fired, LaunchSource::Schedule);
+    }
+    // restart のコマンドの起動し直しと、状態の変化の伝達
+    fn run_supervision(&mut self) {
+        let relaunched = self.supervisor.tick();
+        self.launch_all(relaunched, LaunchSource::Restart);
+
+        let statuses = self.supervisor.statuses();
+        if statuses == self.last_supervision {
+            return;
+        }
+        for status in &statuses {
+            if let SupervisionState::GaveUp { attempts } = status.state
+                && !self.last_supervision.contains(status)
+            {
+                self.notify(notify::restart_gave_up(&status.name, attempts));
             }
         }
+        let _ = self
+            .ui
+            .tx
+            .send(endpoint::UiCommand::UpdateSupervision(statuses.clone()));
+        let _ = self
+            .tray
+            .tx
+            .send(endpoint::TrayCmd::UpdateSupervision(statuses.clone()));
+        self.last_supervision = statuses;
+    }
+    // 再起動待ちと待機中の起動を進め、待機中の起動が変われば UI に伝達する
+    fn run_launches(&mut self) {
+        let events = self.launches.poll(&self.processes.snapshot());
+        self.handle_launch_events(events);
+
+        let waiting = self.launches.waiting();
+        if waiting != self.last_waiting {
+            let _ = self
+                .ui
+                .tx
+                .send(endpoint::UiCommand::UpdateWaiting(waiting.clone()));
+            self.last_waiting = waiting;
+        }
+    }
+    // UI 以外からの起動も、UI からの起動と同じく重複起動と同時実行数の上限を確認してから起動する
+    fn launch_all(&mut self, commands: Vec<CommandSpec>, source: LaunchSource) {
+        for command in commands {
+            let events = self
+                .launches
+                .launch(&command, source, &self.processes.snapshot());
+            self.handle_launch_events(events);
+        }
+    }
+    fn handle_launch_events(&mut self, events: Vec<LaunchEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        for event in events {
+            match event {
+                LaunchEvent::Spawned { spawned, source } => {
+                    if source.is_user_initiated() {
+                        let _ = self.ui.tx.send(endpoint::UiCommand::Launched {
+                            name: spawned.name.clone(),
+                            log: spawned.log.clone(),
+                        });
+                    }
+                    if source != LaunchSource::Restart {
+                        self.supervisor.started(&spawned.name);
+                    }
+                    self.register(spawned, source);
+                }
+                LaunchEvent::Failed {
+                    name,
+                    argv,
+                    error,
+                    declined,
+                    source,
+                } => {
+                    if source == LaunchSource::Restart {
+                        self.supervisor.launch_failed(&name, declined);
+                    }
+                    let record = HistoryRecord::spawn_error(&name, argv, source);
+                    history::append_or_warn(&if declined { record.declined() } else { record });
+                    self.notify(notify::spawn_failed(&name, &error));
+                }
+                LaunchEvent::AlreadyRunning { name, pid, source } => {
+                    // 定期実行などで毎回通知されないよう、利用者が起動したものだけ通知する
+                    if source.is_user_initiated() {
+                        self.notify(notify::already_running(&name, pid));
+                    }
+                }
+                LaunchEvent::Terminate(pid) => self.terminate_process(pid),
+                LaunchEvent::Kill(pid) => self.kill_process(pid),
+            }
+        }
+        self.broadcast_processes();
+    }
+    // 一覧に登録し、起動の記録を履歴に追記する（終了の記録は回収時に追記する）
+    fn register(&mut self, spawned: runner::Spawned, source: LaunchSource) {
+        let info = self.processes.register(spawned, source);
+        history::append_or_warn(&HistoryRecord::from_process(info));
+    }
+    // 取り込んだ標準出力をクリップボードにコピーする、または UI に表示する
+    fn deliver_output(&mut self, output: CommandOutput) {
+        match output.mode {
+            OutputMode::Clipboard => {
+                // 失敗した場合は終了時の通知で伝わるため、コピーしない
+                if output.exit_code != Some(0) {
+                    return;
+                }
+                match self.clipboard.set_text(output.text.trim_end()) {
+                    Ok(()) => self.notify(notify::copied_output(&output)),
+                    Err(e) => warn!("出力をクリップボードにコピーできません: {e:?}"),
+                }
+            }
+            _ => {
+                let _ = self.ui.tx.send(endpoint::UiCommand::ShowOutput(output));
+                self.state.shown_by = LaunchSource::Ui;
+                self.request_show_window();
+            }
+        }
+    }
+    fn notify(&self, notification: Notification) {
+        if let Err(e) = self.notifier.notify(notification) {
+            warn!("通知を送信できません: {e:?}");
+        }
+    }
+    fn broadcast_processes(&self) {
+        let snapshot = self.processes.snapshot();
+        let _ = self
+            .ui
+            .tx
+            .send(endpoint::UiCommand::UpdateProcesses(snapshot.clone()));
+        let _ = self
+            .tray
+            .tx
+            .send(endpoint::TrayCmd::UpdateProcesses(snapshot));
+    }
+    fn terminate_process(&mut self, pid: u32) {
+        info!("プロセスの終了要求: pid={}", pid);
+        self.supervisor.stopped_by_user(pid);
+        if let Err(e) = self.processes.terminate(pid) {
+            warn!("プロセスを終了できません: {e:?}");
+        }
+    }
+    fn kill_process(&mut self, pid: u32) {
+        info!("プロセスツリーの強制終了要求: pid={}", pid);
+        self.supervisor.stopped_by_user(pid);
+        if let Err(e) = self.processes.kill_tree(pid) {
+            warn!("プロセスを強制終了できません: {e:?}");
+        }
     }
 
     fn toggle_window(&mut self) {
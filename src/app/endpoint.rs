@@ -1,14 +1,37 @@
+use std::path::PathBuf;
+
 use windows_sys::Win32::Foundation::HWND;
 
+use crate::app::pipeline::WaitingLaunch;
+use crate::app::processes::{CommandOutput, ProcessInfo};
+use crate::app::supervisor::SupervisionStatus;
+use crate::model::commands::CommandSpec;
+use crate::notify::Notification;
+
 // controller <-> ui
 pub enum UiEvent {
     HwndReady(HWND),
     ThreadIdReady(u32),
     LostFocus,
+    // resolve 済みのコマンドの起動を依頼する（重複起動の判定と起動は Controller が行う）
+    Launch(Box<CommandSpec>),
+    // 同時実行数の上限で待機中の起動を取り消す
+    CancelWaiting(u64),
+    TerminateProcess(u32),
+    KillProcess(u32),
 }
 
 pub enum UiCommand {
     ForcusInput,
+    UpdateProcesses(Vec<ProcessInfo>),
+    // output: show のコマンドの出力を表示する
+    ShowOutput(CommandOutput),
+    // restart のコマンドの起動し直しの状態
+    UpdateSupervision(Vec<SupervisionStatus>),
+    // UI から依頼したコマンドを起動した（候補の並び替えとログの表示に使う）
+    Launched { name: String, log: Option<PathBuf> },
+    // 同時実行数の上限に達して待機中の起動
+    UpdateWaiting(Vec<WaitingLaunch>),
 }
 
 pub struct UiEndpoint {
@@ -73,16 +96,21 @@ pub fn create_hotkey_endpoints() -> (HotkeyEndpoint, HotkeyHandle) {
     )
 }
 
-// controller <- tasktray
-// 現状は表示・終了のコマンドの単方向通信
+// controller <-> tasktray
 
 pub enum TrayEvent {
     ShowWindow,
     Quit,
+    TerminateProcess(u32),
+    KillProcess(u32),
 }
 
 pub enum TrayCmd {
     Finish,
+    UpdateProcesses(Vec<ProcessInfo>),
+    UpdateSupervision(Vec<SupervisionStatus>),
+    // バルーン通知の表示
+    Notify(Notification),
 }
 
 pub struct TrayHandle {
@@ -0,0 +1,209 @@
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use serde::Deserialize;
+
+use crate::elevation;
+use crate::history::{HistoryRecord, LaunchSource};
+use crate::model::commands::{CommandSpec, Commands};
+use crate::model::duration::ConfigDuration;
+use crate::runner::{self, LaunchDeps};
+
+// setting.yaml の `on_start` / `on_exit` / `shutdown_timeout`
+#[derive(Debug, Clone, Deserialize)]
+pub struct HooksConfig {
+    // 常駐アプリの起動時（UI の準備ができた後）に上から順に起動するコマンド名
+    #[serde(default)]
+    on_start: Vec<String>,
+    // 終了時に上から順に実行し、それぞれの終了を待つコマンド名
+    #[serde(default)]
+    on_exit: Vec<String>,
+    // on_exit 全体の待ち時間の上限。過ぎたら実行中のものを強制終了し、残りは実行しない
+    #[serde(default = "default_shutdown_timeout")]
+    shutdown_timeout: ConfigDuration,
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            on_start: Vec::new(),
+            on_exit: Vec::new(),
+            shutdown_timeout: default_shutdown_timeout(),
+        }
+    }
+}
+
+fn default_shutdown_timeout() -> ConfigDuration {
+    ConfigDuration::from_secs(10)
+}
+
+// 常駐アプリの起動時・終了時と、コマンドの終了後（after）に実行するコマンド
+pub struct LifecycleHooks {
+    on_start: Vec<CommandSpec>,
+    on_exit: Vec<CommandSpec>,
+    shutdown_timeout: Duration,
+    // after のコマンドは別スレッドで実行するため複製して渡す
+    deps: LaunchDeps,
+    started: bool,
+}
+
+impl LifecycleHooks {
+    // 確認が必要なコマンドは before / after と同じ扱いで使わない（CommandSpec::hook_command を参照）
+    pub fn new(config: &HooksConfig, commands: &Commands, deps: LaunchDeps) -> Self {
+        let find = |names: &[String], hook: &str| -> Vec<CommandSpec> {
+            names
+                .iter()
+                .filter_map(|name| {
+                    let Some(command) = commands.find_by_name(name) else {
+                        warn!("{hook} のコマンドが見つかりません: {name}");
+                        return None;
+                    };
+                    if command.needs_confirmation() {
+                        warn!("{hook} に確認が必要なコマンドは指定できません: {name}");
+                        return None;
+                    }
+                    Some(command.clone())
+                })
+                .collect()
+        };
+        Self {
+            on_start: find(&config.on_start, "on_start"),
+            on_exit: find(&config.on_exit, "on_exit"),
+            shutdown_timeout: config.shutdown_timeout.get(),
+            deps,
+            started: false,
+        }
+    }
+
+    // on_start のコマンド（上から順に起動を依頼する。終了は待たない）。2 回目以降は空
+    pub fn run_start(&mut self) -> Vec<CommandSpec> {
+        if std::mem::replace(&mut self.started, true) {
+            return Vec::new();
+        }
+        for command in &self.on_start {
+            info!("起動時のコマンドを実行します: {:?}", command.name());
+        }
+        self.on_start.clone()
+    }
+
+    // 終了したプロセスの after のコマンドを実行する
+    // 順に終了を待つため、Controller のループを止めないよう別スレッドで実行する
+    pub fn run_after(&self, hooks: Vec<CommandSpec>, exit_code: Option<i32>, duration: Duration) {
+        if hooks.is_empty() {
+            return;
+        }
+        let deps = self.deps.clone();
+        std::thread::spawn(move || {
+            runner::run_after_hooks(&deps, &hooks, exit_code, duration);
+        });
+    }
+
+    // on_exit のコマンドを順に実行し、それぞれの終了を待つ
+    // 全体で shutdown_timeout を過ぎたら、実行中のものを強制終了して残りを飛ばす
+    // 実行結果は履歴に記録する内容として返す
+    pub fn run_exit(&self) -> Vec<HistoryRecord> {
+        let mut records = Vec::new();
+        let deadline = Instant::now() + self.shutdown_timeout;
+        for command in &self.on_exit {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                warn!(
+                    "終了時のコマンドの待ち時間を過ぎたため実行しません: {:?}",
+                    command.name()
+                );
+                continue;
+            }
+            info!("終了時のコマンドを実行します: {:?}", command.name());
+            let command = runner::resolve(&self.deps, command, "");
+            let mut spawned = match runner::spawn_command(&self.deps, &command) {
+                Ok(spawned) => spawned,
+                Err(e) => {
+                    warn!("終了時のコマンドを起動できません: {e:?}");
+                    let record = HistoryRecord::spawn_error(
+                        command.name(),
+                        runner::resolved_argv(&command),
+                        LaunchSource::Hook,
+                    );
+                    records.push(if elevation::is_declined(&e) {
+                        record.declined()
+                    } else {
+                        record
+                    });
+                    continue;
+                }
+            };
+            let record = HistoryRecord::spawned(
+                &spawned.name,
+                spawned.argv.clone(),
+                LaunchSource::Hook,
+                spawned.child.id(),
+            );
+            let started = Instant::now();
+            match runner::wait_timeout(spawned.child.as_mut(), remaining) {
+                Ok(Some(status)) => {
+                    records.push(record.with_exit(status.code(), false, started.elapsed()));
+                }
+                Ok(None) => {
+                    warn!(
+                        "終了時のコマンドが時間内に終わらないため強制終了します: {:?}",
+                        spawned.name
+                    );
+                    // 子孫プロセスが残らないよう、ツリーごと終了させる
+                    if let Err(e) = spawned.child.kill_tree() {
+                        warn!("終了時のコマンドを終了できません: {e:?}");
+                    }
+                    let _ = spawned.child.try_wait();
+                    records.push(record.with_exit(None, true, started.elapsed()));
+                }
+                Err(e) => warn!("終了時のコマンドの終了を待てません: {e:?}"),
+            }
+        }
+        records
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::RunStatus;
+    use crate::spawner::RecordingSpawner;
+
+    #[test]
+    fn start_hooks_run_once_in_order_and_exit_hooks_stop_at_timeout() {
+        let commands = Commands::new(
+            serde_yaml::from_str(
+                r#"
+- { name: "vpn", program: "vpnclient" }
+- { name: "sync", program: "sync-notes" }
+- { name: "backup", program: "backup" }
+"#,
+            )
+            .unwrap(),
+        );
+        let config: HooksConfig = serde_yaml::from_str(
+            r#"
+on_start: ["sync", "missing", "vpn"]
+on_exit: ["backup", "sync"]
+shutdown_timeout: "50ms"
+"#,
+        )
+        .unwrap();
+        let spawner = RecordingSpawner::default();
+        let mut hooks =
+            LifecycleHooks::new(&config, &commands, LaunchDeps::recording(spawner.clone()));
+
+        let started: Vec<String> = hooks
+            .run_start()
+            .iter()
+            .map(|c| c.name().to_string())
+            .collect();
+        assert_eq!(started, ["sync", "vpn"]);
+        assert!(hooks.run_start().is_empty());
+        // 終了しないプロセスのため、backup で待ち時間を使い切り sync は実行しない
+        let records = hooks.run_exit();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].status, RunStatus::TimedOut);
+        let programs: Vec<String> = spawner.argvs().iter().map(|a| a[0].clone()).collect();
+        assert_eq!(programs, ["backup"]);
+    }
+}
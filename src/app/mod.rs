@@ -1,4 +1,9 @@
 pub mod controller;
 pub mod endpoint;
+pub mod hooks;
 pub mod hotkey;
+pub mod pipeline;
+pub mod processes;
+pub mod scheduler;
+pub mod supervisor;
 pub mod task_tray;
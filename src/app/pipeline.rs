@@ -0,0 +1,440 @@
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use log::{error, info};
+
+use crate::app::processes::{ProcessInfo, TERMINATE_GRACE};
+use crate::elevation;
+use crate::history::LaunchSource;
+use crate::model::commands::{CommandSpec, InstancePolicy};
+use crate::runner::{
+    self, ConcurrencyConfig, InstanceCheck, LaunchDeps, LaunchQueue, Queued, Spawned,
+};
+
+// 起動の依頼から起動までの処理（Controller が保持する）
+// 重複起動の判定、instance: restart の終了待ち、同時実行数の上限による待機をまとめて行う
+// Controller のループから poll を呼ぶため、ランチャーのウィンドウを閉じていても進む
+// 変数の評価、before の実行、起動は別スレッドで行い、結果を poll で受け取る
+pub struct LaunchPipeline {
+    deps: LaunchDeps,
+    queue: LaunchQueue,
+    // instance: restart で、前のプロセスの終了待ちをしている起動
+    pending_restarts: Vec<PendingRestart>,
+    // 別スレッドで起動処理中のもの
+    starting: Vec<Starting>,
+    results_tx: mpsc::Sender<SpawnResult>,
+    results_rx: mpsc::Receiver<SpawnResult>,
+    next_id: u64,
+}
+
+struct Starting {
+    id: u64,
+    name: String,
+    tags: Vec<String>,
+    source: LaunchSource,
+    handle: JoinHandle<()>,
+}
+
+// 起動処理のスレッドから送る結果
+struct SpawnResult {
+    id: u64,
+    // resolve 後のコマンド
+    command: CommandSpec,
+    result: anyhow::Result<Spawned>,
+}
+
+struct PendingRestart {
+    queued: Queued,
+    pids: Vec<u32>,
+    // これを過ぎても終了しない場合は強制終了する
+    deadline: Instant,
+    killed: bool,
+}
+
+// 待機中の起動（UI に表示する）
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaitingLaunch {
+    pub id: u64,
+    pub name: String,
+}
+
+// Controller に処理を依頼する結果
+pub enum LaunchEvent {
+    // 起動した。Controller が一覧に登録する
+    Spawned {
+        spawned: Spawned,
+        source: LaunchSource,
+    },
+    Failed {
+        name: String,
+        argv: Vec<String>,
+        error: String,
+        // 管理者権限への昇格を拒否された
+        declined: bool,
+        source: LaunchSource,
+    },
+    // instance: single のコマンドが実行中のため起動しなかった
+    AlreadyRunning {
+        name: String,
+        pid: u32,
+        source: LaunchSource,
+    },
+    // instance: restart で、実行中のプロセスに終了を要求する
+    Terminate(u32),
+    // 猶予を過ぎても終了しないため強制終了する
+    Kill(u32),
+}
+
+impl LaunchPipeline {
+    pub fn new(concurrency: ConcurrencyConfig, deps: LaunchDeps) -> Self {
+        let (results_tx, results_rx) = mpsc::channel();
+        Self {
+            deps,
+            queue: LaunchQueue::new(concurrency),
+            pending_restarts: Vec::new(),
+            starting: Vec::new(),
+            results_tx,
+            results_rx,
+            next_id: 0,
+        }
+    }
+
+    // 同時実行数の上限に達して待機中の起動。先頭が 1 番目
+    pub fn waiting(&self) -> Vec<WaitingLaunch> {
+        self.queue
+            .waiting()
+            .map(|q| WaitingLaunch {
+                id: q.id,
+                name: q.command.name().to_string(),
+            })
+            .collect()
+    }
+
+    // 待機中の起動を取り消す
+    pub fn cancel(&mut self, id: u64) {
+        if let Some(queued) = self.queue.cancel(id) {
+            info!("{:?}の待機を取り消しました", queued.command.name());
+        }
+    }
+
+    // UI 以外（定期実行、起動時のコマンド、起動し直し）からの起動を依頼する
+    // 変数は起動するスレッドで query を空として resolve する
+    pub fn launch(
+        &mut self,
+        command: &CommandSpec,
+        source: LaunchSource,
+        processes: &[ProcessInfo],
+    ) -> Vec<LaunchEvent> {
+        self.enqueue(command.clone(), source, false, processes)
+    }
+
+    // resolve 済みのコマンドの起動を依頼する
+    // processes は Controller が管理しているプロセス一覧
+    pub fn request(
+        &mut self,
+        command: CommandSpec,
+        source: LaunchSource,
+        processes: &[ProcessInfo],
+    ) -> Vec<LaunchEvent> {
+        self.enqueue(command, source, true, processes)
+    }
+
+    fn enqueue(
+        &mut self,
+        command: CommandSpec,
+        source: LaunchSource,
+        resolved: bool,
+        processes: &[ProcessInfo],
+    ) -> Vec<LaunchEvent> {
+        let name = command.name().to_string();
+        // 起動処理中のもの（待機中、終了待ち、起動中）も実行中として扱い、重複して起動しない
+        if command.instance() != InstancePolicy::Multiple && self.is_in_flight(&name) {
+            info!("{:?}は起動処理中のため起動しません", name);
+            return Vec::new();
+        }
+        self.next_id += 1;
+        let queued = Queued {
+            id: self.next_id,
+            command,
+            source,
+            resolved,
+        };
+        let mut events = Vec::new();
+        match runner::check_instance(&queued.command, processes) {
+            InstanceCheck::Launch => {}
+            InstanceCheck::AlreadyRunning(pid) => {
+                info!("{:?}は実行中のため起動しません: pid={}", name, pid);
+                events.push(LaunchEvent::AlreadyRunning { name, pid, source });
+                return events;
+            }
+            InstanceCheck::Restart(pids) => {
+                info!("{:?}を再起動します: 終了待ち pid={:?}", name, pids);
+                events.extend(pids.iter().map(|pid| LaunchEvent::Terminate(*pid)));
+                self.pending_restarts.push(PendingRestart {
+                    queued,
+                    pids,
+                    deadline: Instant::now() + TERMINATE_GRACE,
+                    killed: false,
+                });
+                return events;
+            }
+        }
+        self.queue.push(queued);
+        self.launch_ready(processes);
+        if let Some(position) = self.queue.waiting().position(|q| q.command.name() == name) {
+            info!(
+                "同時実行数の上限に達しているため待機します: {:?} ({} 番目)",
+                name,
+                position + 1
+            );
+        }
+        events
+    }
+
+    // 再起動待ちと待機中の起動を進め、起動処理の結果を受け取る（Controller のループから呼ぶ）
+    // 起動したプロセスは Controller が一覧に登録するまで起動処理中として数える
+    pub fn poll(&mut self, processes: &[ProcessInfo]) -> Vec<LaunchEvent> {
+        let mut events = self.poll_restarts(processes);
+        self.launch_ready(processes);
+        events.extend(self.poll_starting());
+        events
+    }
+
+    // 待機中、再起動待ち、または起動処理中のコマンドか
+    pub fn is_in_flight(&self, name: &str) -> bool {
+        self.queue.waiting().any(|q| q.command.name() == name)
+            || self
+                .pending_restarts
+                .iter()
+                .any(|p| p.queued.command.name() == name)
+            || self.starting.iter().any(|s| s.name == name)
+    }
+
+    // 再起動待ちのプロセスがすべて終了していれば待機列に入れる
+    // 猶予を過ぎても終了しない場合は強制終了する
+    fn poll_restarts(&mut self, processes: &[ProcessInfo]) -> Vec<LaunchEvent> {
+        let mut events = Vec::new();
+        let now = Instant::now();
+        for pending in std::mem::take(&mut self.pending_restarts) {
+            let still_running: Vec<u32> = processes
+                .iter()
+                .filter(|p| p.is_running() && pending.pids.contains(&p.pid))
+                .map(|p| p.pid)
+                .collect();
+            if still_running.is_empty() {
+                self.queue.push(pending.queued);
+                continue;
+            }
+            let mut pending = pending;
+            if !pending.killed && now >= pending.deadline {
+                pending.killed = true;
+                events.extend(still_running.into_iter().map(LaunchEvent::Kill));
+            }
+            self.pending_restarts.push(pending);
+        }
+        events
+    }
+
+    // 上限に空きができた待機中のコマンドを起動する
+    fn launch_ready(&mut self, processes: &[ProcessInfo]) {
+        let starting: Vec<Vec<String>> = self.starting.iter().map(|s| s.tags.clone()).collect();
+        for queued in self.queue.pop_ready(processes, &starting) {
+            self.spawn(queued);
+        }
+    }
+
+    // 変数の評価、before の実行、起動を別スレッドで行う（Controller のループを止めない）
+    fn spawn(&mut self, queued: Queued) {
+        let Queued {
+            id,
+            command,
+            source,
+            resolved,
+        } = queued;
+        let name = command.name().to_string();
+        let tags = command.tags().to_vec();
+        let deps = self.deps.clone();
+        let tx = self.results_tx.clone();
+        let handle = std::thread::spawn(move || {
+            let command = if resolved {
+                command
+            } else {
+                runner::resolve(&deps, &command, "")
+            };
+            let result = runner::spawn_command(&deps, &command);
+            let _ = tx.send(SpawnResult {
+                id,
+                command,
+                result,
+            });
+        });
+        self.starting.push(Starting {
+            id,
+            name,
+            tags,
+            source,
+            handle,
+        });
+    }
+
+    // 起動処理のスレッドの結果を受け取る
+    fn poll_starting(&mut self) -> Vec<LaunchEvent> {
+        // 先に終了したスレッドを調べる（終了したスレッドの結果は、この後の受信で必ず受け取れる）
+        let finished: Vec<u64> = self
+            .starting
+            .iter()
+            .filter(|s| s.handle.is_finished())
+            .map(|s| s.id)
+            .collect();
+        let mut events = Vec::new();
+        while let Ok(SpawnResult {
+            id,
+            command,
+            result,
+        }) = self.results_rx.try_recv()
+        {
+            let Some(index) = self.starting.iter().position(|s| s.id == id) else {
+                continue;
+            };
+            let source = self.starting.remove(index).source;
+            events.push(match result {
+                Ok(spawned) => {
+                    info!("{:?}を起動しました", command.name());
+                    LaunchEvent::Spawned { spawned, source }
+                }
+                Err(e) => {
+                    error!("起動に失敗しました: {e:?}");
+                    LaunchEvent::Failed {
+                        name: command.name().to_string(),
+                        argv: runner::resolved_argv(&command),
+                        error: format!("{e:#}"),
+                        declined: elevation::is_declined(&e),
+                        source,
+                    }
+                }
+            });
+        }
+        // 結果を送らずに終了したスレッド（パニックなど）は起動処理中から外す
+        let (lost, starting): (Vec<Starting>, Vec<Starting>) = std::mem::take(&mut self.starting)
+            .into_iter()
+            .partition(|s| finished.contains(&s.id));
+        self.starting = starting;
+        for lost in lost {
+            error!("{:?}の起動処理が異常終了しました", lost.name);
+            events.push(LaunchEvent::Failed {
+                name: lost.name,
+                argv: Vec::new(),
+                error: "起動処理が異常終了しました".to_string(),
+                declined: false,
+                source: lost.source,
+            });
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::processes::ProcessState;
+    use crate::spawner::RecordingSpawner;
+
+    fn specs() -> Vec<CommandSpec> {
+        serde_yaml::from_str(
+            r#"
+- { name: "vpn", program: "vpn", instance: single, tags: ["heavy"] }
+- { name: "build", program: "cargo", tags: ["heavy"] }
+- { name: "tail", program: "tail", instance: restart }
+"#,
+        )
+        .unwrap()
+    }
+
+    // 別スレッドの起動処理の結果を待つ
+    fn poll_until_event(
+        pipeline: &mut LaunchPipeline,
+        processes: &[ProcessInfo],
+    ) -> Vec<LaunchEvent> {
+        let deadline = Instant::now() + std::time::Duration::from_secs(5);
+        loop {
+            let events = pipeline.poll(processes);
+            if !events.is_empty() || Instant::now() >= deadline {
+                return events;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+    }
+
+    fn running(name: &str, pid: u32) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: name.into(),
+            argv: vec![],
+            source: LaunchSource::Ui,
+            started_at: chrono::Local::now(),
+            ended_at: None,
+            state: ProcessState::Running,
+            notify: false,
+            timed_out: false,
+            elevation_declined: false,
+            tags: vec!["heavy".into()],
+        }
+    }
+
+    #[test]
+    fn queued_single_instance_is_not_requested_twice() {
+        let specs = specs();
+        let spawner = RecordingSpawner::default();
+        let mut pipeline = LaunchPipeline::new(
+            ConcurrencyConfig::default(),
+            LaunchDeps::recording(spawner.clone()),
+        );
+        // heavy の build が実行中のため、vpn は待機する
+        let processes = [running("build", 1)];
+        assert!(
+            pipeline
+                .request(specs[0].clone(), LaunchSource::Ui, &processes)
+                .is_empty()
+        );
+        // Enter を続けて押しても、待機中の vpn は 1 つだけ
+        assert!(
+            pipeline
+                .request(specs[0].clone(), LaunchSource::Ui, &processes)
+                .is_empty()
+        );
+        assert_eq!(pipeline.waiting().len(), 1);
+
+        // build が終わると、ウィンドウの表示と関係なく poll で起動する
+        let events = poll_until_event(&mut pipeline, &[]);
+        assert!(matches!(events.as_slice(), [LaunchEvent::Spawned { .. }]));
+        assert_eq!(spawner.argvs().len(), 1);
+        assert!(pipeline.waiting().is_empty());
+        assert!(!pipeline.is_in_flight("vpn"));
+    }
+
+    #[test]
+    fn restart_waits_for_previous_process_in_poll() {
+        let specs = specs();
+        let spawner = RecordingSpawner::default();
+        let mut pipeline = LaunchPipeline::new(
+            ConcurrencyConfig::default(),
+            LaunchDeps::recording(spawner.clone()),
+        );
+        let processes = [running("tail", 7)];
+        let events = pipeline.request(specs[2].clone(), LaunchSource::Ui, &processes);
+        assert!(matches!(events.as_slice(), [LaunchEvent::Terminate(7)]));
+        // 終了待ちの間の依頼は重ねない
+        assert!(
+            pipeline
+                .request(specs[2].clone(), LaunchSource::Ui, &processes)
+                .is_empty()
+        );
+        assert!(pipeline.poll(&processes).is_empty());
+        assert!(spawner.argvs().is_empty());
+
+        let events = poll_until_event(&mut pipeline, &[]);
+        assert!(matches!(events.as_slice(), [LaunchEvent::Spawned { .. }]));
+        assert_eq!(spawner.argvs(), [["tail"]]);
+    }
+}
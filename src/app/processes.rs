@@ -0,0 +1,267 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use log::{info, warn};
+
+use crate::history::LaunchSource;
+use crate::model::commands::{CommandSpec, OutputMode};
+use crate::process_control;
+use crate::runner::{OutputCapture, Spawned};
+use crate::spawner::ChildProcess;
+
+// 終了済みのプロセスを一覧に残す件数
+const MAX_FINISHED: usize = 20;
+
+// 終了要求から強制終了に切り替えるまでの猶予
+pub const TERMINATE_GRACE: Duration = Duration::from_secs(5);
+// 終了後に標準出力の読み取り完了を待つ上限
+const CAPTURE_DRAIN_WAIT: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessState {
+    Running,
+    // 終了コード（シグナルで終了した場合は None）
+    Exited(Option<i32>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    // 変数展開後のプログラムと引数
+    pub argv: Vec<String>,
+    pub source: LaunchSource,
+    pub started_at: DateTime<Local>,
+    pub ended_at: Option<DateTime<Local>>,
+    pub state: ProcessState,
+    // 正常終了時にも通知するか
+    pub notify: bool,
+    // timeout により終了させたか
+    pub timed_out: bool,
+    // 管理者権限への昇格を拒否されて終了したか
+    pub elevation_declined: bool,
+    // 同時実行数の上限に使うタグ
+    pub tags: Vec<String>,
+}
+
+impl ProcessInfo {
+    pub fn is_running(&self) -> bool {
+        self.state == ProcessState::Running
+    }
+
+    // 実行時間（終了済みの場合のみ）
+    pub fn runtime(&self) -> Option<chrono::Duration> {
+        self.ended_at.map(|ended_at| ended_at - self.started_at)
+    }
+}
+
+// output: clipboard / show で取り込んだ標準出力
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandOutput {
+    pub name: String,
+    pub mode: OutputMode,
+    pub text: String,
+    pub truncated: bool,
+    pub exit_code: Option<i32>,
+}
+
+// reap で終了を検知したプロセス
+pub struct Finished {
+    pub info: ProcessInfo,
+    pub output: Option<CommandOutput>,
+    // 終了後に実行するコマンド（after）
+    pub after: Vec<CommandSpec>,
+}
+
+struct Entry {
+    info: ProcessInfo,
+    child: Box<dyn ChildProcess>,
+    started: Instant,
+    timeout: Option<Duration>,
+    // timeout による終了要求を送った時刻
+    terminate_requested: Option<Instant>,
+    declined_exit_code: Option<i32>,
+    capture: Option<OutputCapture>,
+    after: Vec<CommandSpec>,
+}
+
+// 常駐アプリから起動したプロセスの管理
+// Controller が保持し、定期的に reap で終了を回収する
+#[derive(Default)]
+pub struct ProcessRegistry {
+    entries: Vec<Entry>,
+}
+
+impl ProcessRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, spawned: Spawned, source: LaunchSource) -> &ProcessInfo {
+        let Spawned {
+            name,
+            child,
+            argv,
+            notify,
+            timeout,
+            declined_exit_code,
+            capture,
+            after,
+            tags,
+            ..
+        } = spawned;
+        let info = ProcessInfo {
+            pid: child.id(),
+            name,
+            argv,
+            source,
+            started_at: Local::now(),
+            ended_at: None,
+            state: ProcessState::Running,
+            notify,
+            timed_out: false,
+            elevation_declined: false,
+            tags,
+        };
+        info!("プロセスを登録しました: {:?} pid={}", info.name, info.pid);
+        self.entries.push(Entry {
+            info,
+            child,
+            started: Instant::now(),
+            timeout,
+            terminate_requested: None,
+            declined_exit_code,
+            capture,
+            after,
+        });
+        &self.entries[self.entries.len() - 1].info
+    }
+
+    // timeout を過ぎたプロセスに終了を要求し、猶予を過ぎても残っていれば強制終了する
+    pub fn enforce_timeouts(&mut self) {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        for entry in self.entries.iter_mut().filter(|e| e.info.is_running()) {
+            let Some(timeout) = entry.timeout else {
+                continue;
+            };
+            match entry.terminate_requested {
+                None if now.duration_since(entry.started) >= timeout => {
+                    info!(
+                        "タイムアウトしたため終了を要求します: {:?} pid={}",
+                        entry.info.name, entry.info.pid
+                    );
+                    entry.info.timed_out = true;
+                    entry.terminate_requested = Some(now);
+                    if let Err(e) = process_control::terminate(entry.info.pid) {
+                        warn!("プロセスを終了できません: pid={}: {e:?}", entry.info.pid);
+                    }
+                }
+                Some(requested) if now.duration_since(requested) >= TERMINATE_GRACE => {
+                    // 強制終了は 1 度だけ行う
+                    entry.timeout = None;
+                    expired.push(entry.info.pid);
+                }
+                _ => {}
+            }
+        }
+        for pid in expired {
+            info!("終了要求に応じないため強制終了します: pid={pid}");
+            if let Err(e) = self.kill_tree(pid) {
+                warn!("プロセスを強制終了できません: {e:?}");
+            }
+        }
+    }
+
+    // 終了したプロセスを回収し、今回終了を検知したプロセスを返す
+    pub fn reap(&mut self) -> Vec<Finished> {
+        let mut finished = Vec::new();
+        for entry in self.entries.iter_mut().filter(|e| e.info.is_running()) {
+            match entry.child.try_wait() {
+                Ok(Some(status)) => {
+                    entry.info.state = ProcessState::Exited(status.code());
+                    entry.info.elevation_declined =
+                        status.code().is_some() && status.code() == entry.declined_exit_code;
+                    entry.info.ended_at = Some(Local::now());
+                    info!(
+                        "プロセスが終了しました: {:?} pid={} status={}",
+                        entry.info.name, entry.info.pid, status
+                    );
+                    let output = entry.capture.take().and_then(|capture| {
+                        let captured = capture.wait(CAPTURE_DRAIN_WAIT);
+                        if captured.is_none() {
+                            warn!("標準出力を読み取れません: pid={}", entry.info.pid);
+                        }
+                        captured.map(|captured| CommandOutput {
+                            name: entry.info.name.clone(),
+                            mode: capture.mode,
+                            text: captured.text,
+                            truncated: captured.truncated,
+                            exit_code: status.code(),
+                        })
+                    });
+                    finished.push(Finished {
+                        info: entry.info.clone(),
+                        output,
+                        after: std::mem::take(&mut entry.after),
+                    });
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    warn!(
+                        "プロセスの状態を取得できません: pid={}: {e:?}",
+                        entry.info.pid
+                    );
+                }
+            }
+        }
+        if !finished.is_empty() {
+            self.trim_finished();
+        }
+        finished
+    }
+
+    pub fn snapshot(&self) -> Vec<ProcessInfo> {
+        self.entries.iter().map(|e| e.info.clone()).collect()
+    }
+
+    pub fn running_count(&self) -> usize {
+        self.entries.iter().filter(|e| e.info.is_running()).count()
+    }
+
+    // 終了を要求する
+    pub fn terminate(&mut self, pid: u32) -> anyhow::Result<()> {
+        self.ensure_running(pid)?;
+        process_control::terminate(pid)
+    }
+
+    // 子孫プロセスを含めて強制終了する
+    pub fn kill_tree(&mut self, pid: u32) -> anyhow::Result<()> {
+        self.ensure_running(pid)?;
+        match self.entries.iter_mut().find(|e| e.info.pid == pid) {
+            Some(entry) => entry.child.kill_tree(),
+            None => Ok(()),
+        }
+    }
+
+    fn ensure_running(&self, pid: u32) -> anyhow::Result<()> {
+        match self.entries.iter().find(|e| e.info.pid == pid) {
+            Some(entry) if entry.info.is_running() => Ok(()),
+            Some(_) => anyhow::bail!("プロセスは既に終了しています: pid={pid}"),
+            None => anyhow::bail!("管理対象のプロセスではありません: pid={pid}"),
+        }
+    }
+
+    // 終了済みのエントリを古いものから削除する
+    fn trim_finished(&mut self) {
+        let finished = self.entries.len() - self.running_count();
+        let mut excess = finished.saturating_sub(MAX_FINISHED);
+        self.entries.retain(|e| {
+            if excess > 0 && !e.info.is_running() {
+                excess -= 1;
+                return false;
+            }
+            true
+        });
+    }
+}
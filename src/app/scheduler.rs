@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local};
+use log::{info, warn};
+
+use crate::model::commands::{CommandSpec, Commands};
+use crate::model::schedule::{self, MissedPolicy, OverlapPolicy, ScheduleSpec};
+
+// 現在時刻の取得（テストでは時刻を進められる実装に差し替える）
+pub trait Clock: Send {
+    fn now(&self) -> DateTime<Local>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+// schedule を指定したコマンドの定期実行
+// Controller のループから tick を呼び、予定時刻を過ぎたコマンドを返す
+// 起動は Controller が他の起動と同じく同時実行数の上限を確認してから行う
+pub struct Scheduler {
+    entries: Vec<Entry>,
+    clock: Box<dyn Clock>,
+}
+
+struct Entry {
+    command: CommandSpec,
+    schedule: ScheduleSpec,
+    next: Option<DateTime<Local>>,
+}
+
+impl Scheduler {
+    // last_runs: コマンドごとの最後の定期実行の時刻（実行履歴から）
+    pub fn new(
+        commands: &Commands,
+        last_runs: &HashMap<String, DateTime<Local>>,
+        clock: Box<dyn Clock>,
+    ) -> Self {
+        let now = clock.now();
+        let entries = commands
+            .iter()
+            .filter_map(|command| {
+                let schedule = command.schedule()?.clone();
+                // 確認する人がいないため、確認が必要なコマンドは定期実行しない
+                if command.needs_confirmation() {
+                    warn!(
+                        "{:?}は実行前の確認が必要なため定期実行しません",
+                        command.name()
+                    );
+                    return None;
+                }
+                // 前回の実行があればその次の予定から、なければ今から数える
+                let base = last_runs.get(command.name()).copied().unwrap_or(now);
+                Some(Entry {
+                    next: schedule.next_after(base),
+                    command: command.clone(),
+                    schedule,
+                })
+            })
+            .collect();
+        Self { entries, clock }
+    }
+
+    // schedule list 用。コマンドと次の予定時刻
+    pub fn entries(
+        &self,
+    ) -> impl Iterator<Item = (&CommandSpec, &ScheduleSpec, Option<DateTime<Local>>)> {
+        self.entries
+            .iter()
+            .map(|e| (&e.command, &e.schedule, e.next))
+    }
+
+    // 予定時刻を過ぎたコマンドを返す
+    // busy は重複実行の判定に使う（実行中、または起動処理中のコマンドか）
+    pub fn tick(&mut self, busy: impl Fn(&str) -> bool) -> Vec<CommandSpec> {
+        let now = self.clock.now();
+        let mut fired = Vec::new();
+        for entry in &mut self.entries {
+            let Some(due) = entry.next.filter(|due| *due <= now) else {
+                continue;
+            };
+            // 次の予定は今より後にする（見逃した分をまとめて実行しない）
+            entry.next = entry.schedule.next_after(due.max(now));
+            let name = entry.command.name();
+
+            if schedule::is_missed(due, now) && entry.schedule.missed() == MissedPolicy::Skip {
+                info!(
+                    "{:?}の予定 ({}) を過ぎたため実行しません",
+                    name,
+                    due.format("%Y-%m-%d %H:%M")
+                );
+                continue;
+            }
+            if entry.schedule.overlap() == OverlapPolicy::Skip && busy(name) {
+                info!("{:?}は前回の実行が終わっていないため実行しません", name);
+                continue;
+            }
+
+            info!("{:?}を定期実行します", name);
+            fired.push(entry.command.clone());
+        }
+        fired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use chrono::TimeZone;
+
+    use super::*;
+
+    // テスト側から時刻を進められる時計
+    #[derive(Clone)]
+    struct ManualClock(Arc<Mutex<DateTime<Local>>>);
+
+    impl ManualClock {
+        fn advance(&self, minutes: i64) {
+            *self.0.lock().unwrap() += chrono::Duration::minutes(minutes);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> DateTime<Local> {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn fires_on_schedule_and_applies_missed_and_overlap_policies() {
+        let commands = Commands::new(
+            serde_yaml::from_str(
+                r#"
+- { name: "sync", program: "sync-notes", schedule: { every: 10m } }
+- { name: "cleanup", program: "cleanup", schedule: { cron: "0 * * * *", missed: run_once } }
+- { name: "backup", program: "backup", schedule: { cron: "30 * * * *" } }
+"#,
+            )
+            .unwrap(),
+        );
+        let start = Local.with_ymd_and_hms(2026, 10, 19, 9, 55, 0).unwrap();
+        let clock = ManualClock(Arc::new(Mutex::new(start)));
+        // backup は 8:30 に実行したきりで、9:30 の実行を見逃している
+        let last_runs = HashMap::from([(
+            "backup".to_string(),
+            Local.with_ymd_and_hms(2026, 10, 19, 8, 30, 0).unwrap(),
+        )]);
+        let mut scheduler = Scheduler::new(&commands, &last_runs, Box::new(clock.clone()));
+        let idle = |_: &str| false;
+        let names = |fired: Vec<CommandSpec>| -> Vec<String> {
+            fired.iter().map(|c| c.name().to_string()).collect()
+        };
+
+        // 見逃した backup は missed: skip のため実行しない
+        assert!(scheduler.tick(idle).is_empty());
+        // 10:00 に cleanup、10:05 に sync
+        clock.advance(5);
+        assert_eq!(names(scheduler.tick(idle)), ["cleanup"]);
+        clock.advance(5);
+        assert_eq!(names(scheduler.tick(idle)), ["sync"]);
+
+        // sync が実行中（または起動待ち）の間は次の実行を飛ばす
+        clock.advance(10);
+        assert!(scheduler.tick(|name| name == "sync").is_empty());
+
+        // スリープなどで 11:00 を大きく過ぎた場合、cleanup は 1 回だけ実行する
+        // sync は missed: skip のため実行しない
+        clock.advance(120);
+        assert_eq!(names(scheduler.tick(idle)), ["cleanup"]);
+        let next: Vec<_> = scheduler.entries().map(|(_, _, next)| next).collect();
+        assert!(next.iter().all(|t| t.unwrap() > clock.now()));
+    }
+}
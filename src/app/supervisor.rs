@@ -0,0 +1,324 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use log::{info, warn};
+
+use crate::app::processes::{ProcessInfo, ProcessState};
+use crate::app::scheduler::Clock;
+use crate::model::commands::{CommandSpec, Commands, InstancePolicy};
+
+// これ以上続けて動いていたプロセスは安定していたとみなし、回数と待ち時間を最初に戻す
+pub const STABLE_RUNTIME: Duration = Duration::from_secs(60);
+
+// 起動し直しの状態（UI とタスクトレイに表示する）
+#[derive(Debug, Clone, PartialEq)]
+pub enum SupervisionState {
+    // 実行中、または起動し直す必要がない
+    Idle,
+    // attempt 回目の起動し直しを at に行う
+    Waiting { attempt: u32, at: DateTime<Local> },
+    // max_retries 回起動し直しても終了したため諦めた
+    GaveUp { attempts: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SupervisionStatus {
+    pub name: String,
+    pub max_retries: u32,
+    pub state: SupervisionState,
+}
+
+// restart を指定したコマンドの監視
+// Controller が終了を検知したら exited を呼び、ループから tick を呼んで起動し直すコマンドを受け取る
+// 状態はコマンドごとに持つため、instance: multiple のコマンドは監視しない
+pub struct Supervisor {
+    entries: Vec<Entry>,
+    // 利用者が終了させたプロセス（起動し直さない）
+    stopped: HashSet<u32>,
+    clock: Box<dyn Clock>,
+}
+
+struct Entry {
+    command: CommandSpec,
+    // 続けて起動し直した回数
+    retries: u32,
+    state: SupervisionState,
+}
+
+impl Entry {
+    // 次の起動し直しを待つ。上限に達していれば諦める
+    fn schedule_retry(&mut self, now: DateTime<Local>) {
+        let Some(restart) = self.command.restart().cloned() else {
+            return;
+        };
+        if self.retries >= restart.max_retries() {
+            warn!(
+                "{:?}は {} 回起動し直しても終了したため、起動し直しをやめます",
+                self.command.name(),
+                self.retries
+            );
+            self.state = SupervisionState::GaveUp {
+                attempts: self.retries,
+            };
+            return;
+        }
+        self.retries += 1;
+        let delay = restart.delay(self.retries);
+        let at = now + chrono::Duration::from_std(delay).unwrap_or(chrono::Duration::zero());
+        info!(
+            "{:?}を {:?} 後に起動し直します ({} 回目)",
+            self.command.name(),
+            delay,
+            self.retries
+        );
+        self.state = SupervisionState::Waiting {
+            attempt: self.retries,
+            at,
+        };
+    }
+}
+
+impl Supervisor {
+    pub fn new(commands: &Commands, clock: Box<dyn Clock>) -> Self {
+        let entries = commands
+            .iter()
+            .filter(|command| command.restart().is_some())
+            .filter(|command| {
+                // 複数のプロセスで回数と待ち時間を共有してしまうため、起動し直さない
+                if command.instance() == InstancePolicy::Multiple {
+                    warn!(
+                        "{:?}は instance: multiple のため起動し直しません",
+                        command.name()
+                    );
+                    return false;
+                }
+                true
+            })
+            .map(|command| Entry {
+                command: command.clone(),
+                retries: 0,
+                state: SupervisionState::Idle,
+            })
+            .collect();
+        Self {
+            entries,
+            stopped: HashSet::new(),
+            clock,
+        }
+    }
+
+    // 起動し直しを待っている、または諦めたコマンドの状態
+    pub fn statuses(&self) -> Vec<SupervisionStatus> {
+        self.entries
+            .iter()
+            .filter(|e| e.state != SupervisionState::Idle)
+            .map(|e| SupervisionStatus {
+                name: e.command.name().to_string(),
+                max_retries: e.command.restart().map_or(0, |r| r.max_retries()),
+                state: e.state.clone(),
+            })
+            .collect()
+    }
+
+    // 利用者が終了を要求したプロセス。終了しても起動し直さない
+    pub fn stopped_by_user(&mut self, pid: u32) {
+        self.stopped.insert(pid);
+    }
+
+    // 監視しているコマンドが（起動し直し以外で）起動された
+    // 待っている起動し直しは取りやめ、諦めていた場合は回数を最初に戻す
+    pub fn started(&mut self, name: &str) {
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.command.name() == name)
+            && entry.state != SupervisionState::Idle
+        {
+            entry.retries = 0;
+            entry.state = SupervisionState::Idle;
+        }
+    }
+
+    // プロセスの終了を受けて、起動し直すかを決める
+    pub fn exited(&mut self, process: &ProcessInfo) {
+        let stopped = self.stopped.remove(&process.pid);
+        let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|e| e.command.name() == process.name)
+        else {
+            return;
+        };
+        let Some(restart) = entry.command.restart() else {
+            return;
+        };
+        let ProcessState::Exited(code) = process.state else {
+            return;
+        };
+        // タイムアウトで終了させた場合は終了コードによらず失敗とする
+        let code = if process.timed_out { None } else { code };
+        if stopped || process.elevation_declined || !restart.should_restart(code) {
+            entry.retries = 0;
+            entry.state = SupervisionState::Idle;
+            return;
+        }
+        let stable = process
+            .runtime()
+            .and_then(|d| d.to_std().ok())
+            .is_some_and(|d| d >= STABLE_RUNTIME);
+        if stable {
+            entry.retries = 0;
+        }
+        info!("{:?}が終了しました", process.name);
+        entry.schedule_retry(self.clock.now());
+    }
+
+    // 起動し直しに失敗した（before の失敗を含む）
+    // 終了した場合と同じく回数に数えて次を待ち、上限に達したら諦める。昇格を拒否された場合はやめる
+    pub fn launch_failed(&mut self, name: &str, declined: bool) {
+        let now = self.clock.now();
+        let Some(entry) = self.entries.iter_mut().find(|e| e.command.name() == name) else {
+            return;
+        };
+        if declined {
+            entry.retries = 0;
+            entry.state = SupervisionState::Idle;
+            return;
+        }
+        info!("{:?}の起動し直しに失敗しました", name);
+        entry.schedule_retry(now);
+    }
+
+    // 待ち時間を過ぎた、起動し直すコマンド
+    // 起動に失敗した場合は Controller が launch_failed を呼ぶ
+    pub fn tick(&mut self) -> Vec<CommandSpec> {
+        let now = self.clock.now();
+        let mut launched = Vec::new();
+        for entry in &mut self.entries {
+            let SupervisionState::Waiting { at, .. } = entry.state else {
+                continue;
+            };
+            if at > now {
+                continue;
+            }
+            entry.state = SupervisionState::Idle;
+            launched.push(entry.command.clone());
+        }
+        launched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::history::LaunchSource;
+
+    #[derive(Clone)]
+    struct ManualClock(Arc<Mutex<DateTime<Local>>>);
+
+    impl Clock for ManualClock {
+        fn now(&self) -> DateTime<Local> {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn exited(name: &str, pid: u32, code: Option<i32>) -> ProcessInfo {
+        let now = Local::now();
+        ProcessInfo {
+            pid,
+            name: name.into(),
+            argv: vec![],
+            source: LaunchSource::Ui,
+            started_at: now,
+            ended_at: Some(now),
+            state: ProcessState::Exited(code),
+            notify: false,
+            timed_out: false,
+            elevation_declined: false,
+            tags: vec![],
+        }
+    }
+
+    #[test]
+    fn restarts_with_backoff_until_max_retries() {
+        let commands = Commands::new(
+            serde_yaml::from_str(
+                r#"
+- { name: "tunnel", program: "ssh", restart: { on: failure, max_retries: 2, backoff: 2s } }
+- { name: "sync", program: "syncd", restart: { on: always } }
+- { name: "notes", program: "notepad" }
+"#,
+            )
+            .unwrap(),
+        );
+        let clock = ManualClock(Arc::new(Mutex::new(Local::now())));
+        let mut supervisor = Supervisor::new(&commands, Box::new(clock.clone()));
+        let advance = |secs: i64| *clock.0.lock().unwrap() += chrono::Duration::seconds(secs);
+
+        // 正常終了は on: failure では起動し直さない
+        supervisor.exited(&exited("tunnel", 1, Some(0)));
+        supervisor.exited(&exited("notes", 2, Some(1)));
+        assert!(supervisor.statuses().is_empty());
+
+        // 1 回目は 2 秒後、2 回目は 4 秒後
+        supervisor.exited(&exited("tunnel", 3, Some(255)));
+        advance(1);
+        assert!(supervisor.tick().is_empty());
+        advance(1);
+        assert_eq!(supervisor.tick().len(), 1);
+        supervisor.exited(&exited("tunnel", 4, None));
+        advance(3);
+        assert!(supervisor.tick().is_empty());
+        advance(1);
+        assert_eq!(supervisor.tick().len(), 1);
+
+        // 上限に達したら諦める
+        supervisor.exited(&exited("tunnel", 5, Some(255)));
+        assert_eq!(
+            supervisor.statuses()[0].state,
+            SupervisionState::GaveUp { attempts: 2 }
+        );
+
+        // 利用者が終了させたものは on: always でも起動し直さない
+        supervisor.stopped_by_user(6);
+        supervisor.exited(&exited("sync", 6, Some(0)));
+        assert_eq!(supervisor.statuses().len(), 1);
+    }
+
+    #[test]
+    fn failed_relaunch_counts_against_retries() {
+        let commands = Commands::new(
+            serde_yaml::from_str(
+                r#"
+- { name: "tunnel", program: "ssh", restart: { max_retries: 2, backoff: 1s } }
+- { name: "worker", program: "worker", instance: multiple, restart: { on: always } }
+"#,
+            )
+            .unwrap(),
+        );
+        let clock = ManualClock(Arc::new(Mutex::new(Local::now())));
+        let mut supervisor = Supervisor::new(&commands, Box::new(clock.clone()));
+        let advance = |secs: i64| *clock.0.lock().unwrap() += chrono::Duration::seconds(secs);
+
+        // instance: multiple は監視しない
+        supervisor.exited(&exited("worker", 1, Some(0)));
+        assert!(supervisor.statuses().is_empty());
+
+        supervisor.exited(&exited("tunnel", 2, Some(1)));
+        advance(1);
+        assert_eq!(supervisor.tick().len(), 1);
+        // 起動し直しに失敗しても監視を続け、2 回目を待つ
+        supervisor.launch_failed("tunnel", false);
+        assert!(matches!(
+            supervisor.statuses()[0].state,
+            SupervisionState::Waiting { attempt: 2, .. }
+        ));
+        advance(2);
+        assert_eq!(supervisor.tick().len(), 1);
+        supervisor.launch_failed("tunnel", false);
+        assert_eq!(
+            supervisor.statuses()[0].state,
+            SupervisionState::GaveUp { attempts: 2 }
+        );
+    }
+}
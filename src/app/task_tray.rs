@@ -1,14 +1,29 @@
-use log::info;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use log::{error, info, warn};
 use tray_icon::{
     Icon, TrayIconBuilder,
-    menu::{Menu, MenuEvent, MenuItem},
+    menu::{Menu, MenuEvent, MenuId, MenuItem, Submenu},
 };
 use windows_sys::Win32::{
+    Foundation::{GetLastError, HWND},
     System::Com::CoUninitialize,
-    UI::WindowsAndMessaging::{DispatchMessageW, MSG, PM_REMOVE, PeekMessageW, TranslateMessage},
+    UI::Shell::{
+        NIF_ICON, NIF_INFO, NIF_TIP, NIIF_ERROR, NIIF_INFO, NIM_ADD, NIM_DELETE, NIM_MODIFY,
+        NOTIFYICONDATAW, Shell_NotifyIconW,
+    },
+    UI::WindowsAndMessaging::{
+        CreateIcon, CreateWindowExW, DestroyIcon, DestroyWindow, DispatchMessageW, HICON,
+        HWND_MESSAGE, MSG, PM_REMOVE, PeekMessageW, TranslateMessage,
+    },
 };
 
 use crate::app::endpoint::{TrayCmd, TrayEndpoint, TrayEvent};
+use crate::app::processes::ProcessInfo;
+use crate::app::supervisor::{SupervisionState, SupervisionStatus};
+use crate::notify::{Notification, NotifyLevel};
 
 // 推奨: 32x32 透過PNG
 // build.rs でコンパイル時に埋め込み
@@ -36,8 +51,12 @@ impl TaskTray {
         let icon = load_tray_icon_from_embedded_png()?;
         let menu = Menu::new();
         let item_show = MenuItem::new("Show Window", true, None);
+        let processes_menu = Submenu::new("Running Processes", false);
+        let restart_menu = Submenu::new("Restarting", false);
         let item_quit = MenuItem::new("Quit", true, None);
-        let _ = menu.append_items(&[&item_show, &item_quit]);
+        let _ = menu.append_items(&[&item_show, &processes_menu, &restart_menu, &item_quit]);
+        // プロセス操作のメニュー ID と、送信するイベントの対応
+        let mut process_actions: HashMap<MenuId, TrayEvent> = HashMap::new();
 
         let show_id = item_show.id().clone();
         let quit_id = item_quit.id().clone();
@@ -47,6 +66,14 @@ impl TaskTray {
             .with_menu(Box::new(menu))
             .with_icon(icon)
             .build()?;
+        // 通知用のアイコン（作成できない場合は通知だけ諦める）
+        let mut balloon = match BalloonIcon::new() {
+            Ok(balloon) => Some(balloon),
+            Err(e) => {
+                error!("通知用のアイコンを作成できません: {e:?}");
+                None
+            }
+        };
 
         let menu_rx = MenuEvent::receiver();
         // let tray_rx = TrayIconEvent::receiver();
@@ -64,6 +91,8 @@ impl TaskTray {
                 } else if event.id == quit_id {
                     // info!("Quit menu item clicked");
                     let _ = self.endpoint.tx.send(TrayEvent::Quit);
+                } else if let Some(action) = process_actions.remove(&event.id) {
+                    let _ = self.endpoint.tx.send(action);
                 }
             }
             // トレイイベント（必要なら）
@@ -79,8 +108,27 @@ impl TaskTray {
                         info!("コントローラーからの終了処理受信");
                         break;
                     }
+                    TrayCmd::UpdateProcesses(processes) => {
+                        process_actions = rebuild_process_menu(&processes_menu, &processes);
+                    }
+                    TrayCmd::UpdateSupervision(statuses) => {
+                        rebuild_restart_menu(&restart_menu, &statuses);
+                    }
+                    TrayCmd::Notify(notification) => {
+                        if let Some(balloon) = &mut balloon
+                            && let Err(e) = balloon.show(&notification)
+                        {
+                            error!(
+                                "バルーン通知を表示できません: {:?}: {e:?}",
+                                notification.title
+                            );
+                        }
+                    }
                 }
             }
+            if let Some(balloon) = &mut balloon {
+                balloon.expire();
+            }
             // CPU負荷を抑えるために短いスリープを挿入
             std::thread::sleep(std::time::Duration::from_millis(20));
         }
@@ -91,6 +139,202 @@ impl TaskTray {
     }
 }
 
+// 実行中のプロセスごとに「終了」「強制終了」のサブメニューを作り直す
+fn rebuild_process_menu(menu: &Submenu, processes: &[ProcessInfo]) -> HashMap<MenuId, TrayEvent> {
+    while menu.remove_at(0).is_some() {}
+
+    let mut actions = HashMap::new();
+    let running: Vec<&ProcessInfo> = processes.iter().filter(|p| p.is_running()).collect();
+    for process in &running {
+        let sub = Submenu::new(format!("{} (pid {})", process.name, process.pid), true);
+        let terminate = MenuItem::new("Terminate", true, None);
+        let kill = MenuItem::new("Kill Process Tree", true, None);
+        let _ = sub.append_items(&[&terminate, &kill]);
+        actions.insert(
+            terminate.id().clone(),
+            TrayEvent::TerminateProcess(process.pid),
+        );
+        actions.insert(kill.id().clone(), TrayEvent::KillProcess(process.pid));
+        let _ = menu.append(&sub);
+    }
+    menu.set_enabled(!running.is_empty());
+    actions
+}
+
+// 起動し直しを待っている、または諦めたコマンドを表示する（選択はできない）
+fn rebuild_restart_menu(menu: &Submenu, statuses: &[SupervisionStatus]) {
+    while menu.remove_at(0).is_some() {}
+
+    for status in statuses {
+        let text = match &status.state {
+            SupervisionState::Waiting { attempt, at } => format!(
+                "{}: retry {}/{} at {}",
+                status.name,
+                attempt,
+                status.max_retries,
+                at.format("%H:%M:%S")
+            ),
+            SupervisionState::GaveUp { attempts } => {
+                format!("{}: gave up after {} retries", status.name, attempts)
+            }
+            SupervisionState::Idle => continue,
+        };
+        let _ = menu.append(&MenuItem::new(text, false, None));
+    }
+    menu.set_enabled(!statuses.is_empty());
+}
+
+// 通知用のアイコンをタスクトレイに置いておく時間（最後の通知から）
+const BALLOON_LIFETIME: Duration = Duration::from_secs(10);
+// 通知用のウィンドウに登録するアイコンの ID（ウィンドウごとに一意であればよい）
+const BALLOON_ICON_UID: u32 = 1;
+
+// バルーン通知の表示に使うアイコン
+// tray-icon は Shell_NotifyIcon に登録した ID を公開していないため、
+// メッセージ専用ウィンドウに通知用のアイコンを自前で登録し、通知を表示している間だけタスクトレイに置く
+struct BalloonIcon {
+    hwnd: HWND,
+    hicon: HICON,
+    // 最後に通知を表示した時刻（アイコンを登録している間のみ）
+    shown_at: Option<Instant>,
+}
+
+impl BalloonIcon {
+    fn new() -> anyhow::Result<Self> {
+        let class: Vec<u16> = "STATIC\0".encode_utf16().collect();
+        unsafe {
+            let hwnd = CreateWindowExW(
+                0,
+                class.as_ptr(),
+                std::ptr::null(),
+                0,
+                0,
+                0,
+                0,
+                0,
+                HWND_MESSAGE,
+                0,
+                0,
+                std::ptr::null(),
+            );
+            if hwnd == 0 {
+                bail!("ウィンドウを作成できません: {}", GetLastError());
+            }
+            let hicon = create_icon(TRAY_RGBA, TRAY_WIDTH, TRAY_HEIGHT);
+            if hicon == 0 {
+                let code = GetLastError();
+                DestroyWindow(hwnd);
+                bail!("アイコンを作成できません: {code}");
+            }
+            Ok(Self {
+                hwnd,
+                hicon,
+                shown_at: None,
+            })
+        }
+    }
+
+    // バルーン通知を表示する（アイコンが未登録なら登録する）
+    fn show(&mut self, notification: &Notification) -> anyhow::Result<()> {
+        let mut nid = self.icon_data();
+        nid.uFlags = NIF_ICON | NIF_TIP | NIF_INFO;
+        nid.hIcon = self.hicon;
+        copy_wide(&mut nid.szTip, "rs-command-launcher");
+        nid.dwInfoFlags = match notification.level {
+            NotifyLevel::Info => NIIF_INFO,
+            NotifyLevel::Error => NIIF_ERROR,
+        };
+        copy_wide(&mut nid.szInfoTitle, &notification.title);
+        copy_wide(&mut nid.szInfo, &notification.body);
+
+        let message = if self.shown_at.is_some() {
+            NIM_MODIFY
+        } else {
+            NIM_ADD
+        };
+        if unsafe { Shell_NotifyIconW(message, &nid) } == 0 {
+            bail!("Shell_NotifyIconW が失敗しました: {}", unsafe {
+                GetLastError()
+            });
+        }
+        self.shown_at = Some(Instant::now());
+        Ok(())
+    }
+
+    // 最後の通知から時間が経ったアイコンをタスクトレイから取り除く
+    fn expire(&mut self) {
+        if self
+            .shown_at
+            .is_some_and(|shown_at| shown_at.elapsed() >= BALLOON_LIFETIME)
+        {
+            self.remove();
+        }
+    }
+
+    fn remove(&mut self) {
+        if self.shown_at.take().is_none() {
+            return;
+        }
+        let nid = self.icon_data();
+        if unsafe { Shell_NotifyIconW(NIM_DELETE, &nid) } == 0 {
+            warn!("通知用のアイコンを取り除けません");
+        }
+    }
+
+    fn icon_data(&self) -> NOTIFYICONDATAW {
+        let mut nid: NOTIFYICONDATAW = unsafe { std::mem::zeroed() };
+        nid.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as u32;
+        nid.hWnd = self.hwnd;
+        nid.uID = BALLOON_ICON_UID;
+        nid
+    }
+}
+
+impl Drop for BalloonIcon {
+    fn drop(&mut self) {
+        self.remove();
+        unsafe {
+            DestroyIcon(self.hicon);
+            DestroyWindow(self.hwnd);
+        }
+    }
+}
+
+// RGBA の画像から HICON を作成する（失敗した場合は 0）
+fn create_icon(rgba: &[u8], width: u32, height: u32) -> HICON {
+    let mut bgra = rgba.to_vec();
+    // AND マスクは 1 ピクセル 1 ビットのモノクロで、各行は WORD 境界に揃える
+    // 透明（アルファ値が 0）のピクセルのビットを立てる
+    let stride = width.div_ceil(16) as usize * 2;
+    let mut and_mask = vec![0u8; stride * height as usize];
+    for (i, pixel) in bgra.chunks_exact_mut(4).enumerate() {
+        let (x, y) = (i % width as usize, i / width as usize);
+        if pixel[3] == 0 {
+            and_mask[y * stride + x / 8] |= 0x80 >> (x % 8);
+        }
+        pixel.swap(0, 2);
+    }
+    unsafe {
+        CreateIcon(
+            0,
+            width as i32,
+            height as i32,
+            1,
+            32,
+            and_mask.as_ptr(),
+            bgra.as_ptr(),
+        )
+    }
+}
+
+// 固定長の UTF-16 バッファへ NUL 終端付きでコピーする（収まらない分は切り捨て）
+fn copy_wide(dst: &mut [u16], s: &str) {
+    let len = dst.len() - 1;
+    for (d, c) in dst.iter_mut().zip(s.encode_utf16().take(len)) {
+        *d = c;
+    }
+}
+
 fn pump_win32_messages_once() {
     // このスレッドに配送されている Win32 メッセージを捌く
     unsafe {
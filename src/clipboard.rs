@@ -0,0 +1,135 @@
+use anyhow::Context;
+
+// クリップボードへのアクセス
+// テストではメモリ上の代替実装に差し替える
+pub trait ClipboardProvider: Send + Sync {
+    fn get_text(&self) -> anyhow::Result<String>;
+    fn set_text(&self, text: &str) -> anyhow::Result<()>;
+}
+
+// OS のクリップボード
+// Linux ではコピーした内容を保持するために Clipboard を生かしておく必要があるため、
+// 最初に開いたものを使い回す
+#[derive(Default)]
+pub struct SystemClipboard {
+    inner: std::sync::Mutex<Option<arboard::Clipboard>>,
+}
+
+impl SystemClipboard {
+    fn with_clipboard<T>(
+        &self,
+        f: impl FnOnce(&mut arboard::Clipboard) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let mut inner = self
+            .inner
+            .lock()
+            .map_err(|_| anyhow::anyhow!("クリップボードのロックに失敗しました"))?;
+        let clipboard = match inner.take() {
+            Some(clipboard) => clipboard,
+            None => arboard::Clipboard::new().context("クリップボードを開けません")?,
+        };
+        f(inner.insert(clipboard))
+    }
+}
+
+impl ClipboardProvider for SystemClipboard {
+    fn get_text(&self) -> anyhow::Result<String> {
+        self.with_clipboard(|clipboard| {
+            clipboard
+                .get_text()
+                .context("クリップボードからテキストを取得できません")
+        })
+    }
+
+    fn set_text(&self, text: &str) -> anyhow::Result<()> {
+        self.with_clipboard(|clipboard| {
+            clipboard
+                .set_text(text)
+                .context("クリップボードにコピーできません")
+        })
+    }
+}
+
+// CLI の終了後もコピーした内容を残す
+// Linux ではコピーしたプロセスが終了すると内容が失われるため、自分自身を子プロセスとして起動し、
+// 他のアプリがコピーするまで内容を保持させる（HOLD_ARG を付けて起動したものが hold_from_stdin を呼ぶ）
+pub fn set_text_for_cli(text: &str) -> anyhow::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::io::Write;
+        use std::process::{Command, Stdio};
+
+        let exe = std::env::current_exe().context("実行ファイルのパスを取得できません")?;
+        let mut child = Command::new(exe)
+            .arg(HOLD_ARG)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .current_dir("/")
+            .spawn()
+            .context("クリップボードを保持するプロセスを起動できません")?;
+        let mut stdin = child.stdin.take().context("標準入力を開けません")?;
+        stdin
+            .write_all(text.as_bytes())
+            .context("クリップボードを保持するプロセスに出力を渡せません")?;
+        Ok(())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        SystemClipboard::default().set_text(text)
+    }
+}
+
+// クリップボードを保持するプロセスとして起動されたことを示す引数
+#[cfg(target_os = "linux")]
+pub const HOLD_ARG: &str = "__hold-clipboard";
+
+// 標準入力のテキストをコピーし、他のアプリがコピーするまで終了しない
+#[cfg(target_os = "linux")]
+pub fn hold_from_stdin() -> anyhow::Result<()> {
+    use arboard::SetExtLinux;
+    use std::io::Read;
+
+    let mut text = String::new();
+    std::io::stdin()
+        .read_to_string(&mut text)
+        .context("標準入力を読み取れません")?;
+    arboard::Clipboard::new()
+        .context("クリップボードを開けません")?
+        .set()
+        .wait()
+        .text(text)
+        .context("クリップボードにコピーできません")
+}
+
+// テスト用のクリップボード
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryClipboard {
+    text: std::sync::Mutex<Option<String>>,
+}
+
+#[cfg(test)]
+impl MemoryClipboard {
+    pub fn with_text(text: &str) -> Self {
+        Self {
+            text: std::sync::Mutex::new(Some(text.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+impl ClipboardProvider for MemoryClipboard {
+    fn get_text(&self) -> anyhow::Result<String> {
+        self.text
+            .lock()
+            .unwrap()
+            .clone()
+            .context("クリップボードが空です")
+    }
+
+    fn set_text(&self, text: &str) -> anyhow::Result<()> {
+        *self.text.lock().unwrap() = Some(text.to_string());
+        Ok(())
+    }
+}
@@ -3,9 +3,15 @@ use std::path::PathBuf;
 
 use anyhow::Context;
 // use log::info;
+use log::{error, warn};
 use serde::Deserialize;
 
-use crate::model::commands::{CommandSpec, Commands, EnvVars};
+use crate::app::hooks::HooksConfig;
+use crate::model::commands::{CommandSpec, Commands, ProgramStatus};
+use crate::model::vars::{EnvEntries, EnvEntry, EnvVars, ExecCache, Vars, default_list_separator};
+use crate::runner::{self, ConcurrencyConfig};
+use crate::spawner::{OsSpawner, Spawner};
+use crate::terminal::TerminalConfig;
 
 // 設定のパス
 
@@ -29,15 +35,41 @@ fn local_commands_path() -> anyhow::Result<PathBuf> {
 fn env_path() -> anyhow::Result<PathBuf> {
     Ok(config_dir()?.join("env.yaml"))
 }
+
+// 起動したコマンドの出力ログの保存先
+pub fn log_dir() -> anyhow::Result<PathBuf> {
+    Ok(config_dir()?.join("logs"))
+}
+
+// 実行履歴（JSON Lines、追記のみ）
+pub fn history_path() -> anyhow::Result<PathBuf> {
+    Ok(config_dir()?.join("history.jsonl"))
+}
 // 読み込み用の書式
 #[derive(Debug, Clone, Deserialize)]
 struct LoadSettings {
     commands: Vec<CommandSpec>,
+    // `terminal: true` のコマンドを開く端末
+    #[serde(default)]
+    terminal: TerminalConfig,
+    // 常駐アプリの起動時・終了時に実行するコマンド
+    #[serde(flatten)]
+    hooks: HooksConfig,
+    // 同時に実行するコマンド数の上限
+    #[serde(default)]
+    concurrency: ConcurrencyConfig,
 }
 
 impl LoadSettings {
-    fn inner(self) -> Vec<CommandSpec> {
-        self.commands
+    fn inner(
+        self,
+    ) -> (
+        Vec<CommandSpec>,
+        TerminalConfig,
+        HooksConfig,
+        ConcurrencyConfig,
+    ) {
+        (self.commands, self.terminal, self.hooks, self.concurrency)
     }
 }
 
@@ -45,9 +77,21 @@ impl LoadSettings {
 #[derive(Debug, Clone, Deserialize)]
 pub struct Settings {
     commands: Commands,
+    terminal: TerminalConfig,
+    hooks: HooksConfig,
+    concurrency: ConcurrencyConfig,
 }
 
 impl Settings {
+    pub fn terminal(&self) -> &TerminalConfig {
+        &self.terminal
+    }
+    pub fn hooks(&self) -> &HooksConfig {
+        &self.hooks
+    }
+    pub fn concurrency(&self) -> &ConcurrencyConfig {
+        &self.concurrency
+    }
     pub fn commands(self) -> Commands {
         self.commands
     }
@@ -67,12 +111,39 @@ impl LocalCommands {
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct LoadEnv {
-    env: EnvVars,
+    env: EnvEntries,
+    // リスト値をインラインで展開する際の区切り文字
+    #[serde(default = "default_list_separator")]
+    list_separator: String,
 }
 
 impl LoadEnv {
-    fn inner(self) -> EnvVars {
-        self.env
+    // `cache: load` のコマンド出力の変数はここで評価し、`cache: launch` は起動時に回す
+    fn inner(self, spawner: &dyn Spawner) -> Vars {
+        let mut env = EnvVars::new();
+        let mut launch = std::collections::BTreeMap::new();
+        for (name, entry) in self.env {
+            match entry {
+                EnvEntry::Value(value) => {
+                    env.insert(name, value);
+                }
+                EnvEntry::Exec(exec) if exec.cache() == ExecCache::Launch => {
+                    launch.insert(name, exec);
+                }
+                EnvEntry::Exec(exec) => {
+                    match runner::capture_stdout(spawner, exec.argv(), exec.timeout()) {
+                        Ok(out) => {
+                            env.insert(name, out.into());
+                        }
+                        Err(e) => {
+                            // 評価に失敗した変数は未定義として扱う
+                            error!("変数 {name} のコマンド実行に失敗しました: {e:?}");
+                        }
+                    }
+                }
+            }
+        }
+        Vars::new(env, self.list_separator).with_exec_vars(launch)
     }
 }
 
@@ -82,25 +153,41 @@ pub fn load_settings() -> anyhow::Result<Settings> {
     let env_path = env_path()?;
     let local_commands_path = local_commands_path()?;
 
-    let row_settings = load_row_settings(setting_path)?;
-    let env_vars = load_env_vars(env_path)?.inner();
-    let local_commands = load_local_commands(local_commands_path);
+    let row_settings = load_row_settings(setting_path.clone())?;
+    let env_vars = load_env_vars(env_path)?.inner(&OsSpawner::default());
+    let local_commands = load_local_commands(local_commands_path.clone());
     // info!("local_commands : {:?}", local_commands);
 
-    let commands = row_settings.inner();
-    let mut commands = Commands::new(commands);
+    let (commands, terminal, hooks, concurrency) = row_settings.inner();
+    let mut commands = Commands::new(commands).with_source(&setting_path);
     // info!("setting : {:?}", commands);
     if let Some(local_cmds) = local_commands {
-        commands.extend(Commands::new(local_cmds));
+        commands.extend(Commands::new(local_cmds).with_source(&local_commands_path));
     };
     // info!("local_overay : {:?}", commands);
 
+    // before / after をコマンドにする（local_commands.yaml のコマンドも指定できる）
+    let commands = commands.link_hooks();
+
     // 置換処理
 
-    let commands = commands.expand_vars(env_vars);
+    let commands = commands.expand_vars(&env_vars);
     // info!("env_overay : {:?}", commands);
 
-    Ok(Settings { commands })
+    // 実行ファイルの解決（見つからないコマンドは利用不可として残す）
+    let commands = commands.resolve_programs();
+    for cmd in commands.iter() {
+        if let ProgramStatus::Unavailable(reason) = cmd.program_status() {
+            warn!("{:?}は利用できません: {reason}", cmd.name());
+        }
+    }
+
+    Ok(Settings {
+        commands,
+        terminal,
+        hooks,
+        concurrency,
+    })
 }
 
 fn load_row_settings(path: PathBuf) -> anyhow::Result<LoadSettings> {
@@ -0,0 +1,277 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+use log::{error, warn};
+
+use crate::clipboard::ClipboardProvider;
+use crate::model::commands::CommandSpec;
+use crate::model::vars::{EnvValue, EnvVars, Vars, default_list_separator, is_builtin_var};
+use crate::runner;
+use crate::spawner::Spawner;
+
+// 起動時に解決する組み込み変数
+// - ${clipboard}: クリップボードのテキスト
+// - ${query}: ランチャーでコマンド名に続けて入力したテキスト（CLI は `--` 以降の引数）
+// - ${date} / ${date:%Y-%m-%d}: 現在日付（書式指定可）
+// - ${time} / ${time:%H%M}: 現在時刻（書式指定可）
+// - ${uuid}: ランダムな UUID (v4)
+// - ${hostname}: ホスト名
+// - ${launcher.cwd}: ランチャーのカレントディレクトリ
+// - ${exit_code} / ${duration_ms}: after のコマンドのみ。本体の終了コード（ない場合は空）と実行時間
+// あわせて env.yaml の `cache: launch` のコマンド出力の変数もここで評価する
+// プレビュー（explain）ではコマンドの実行とクリップボードの読み取りを行わず、プレースホルダーにする
+pub struct LaunchContext<'a> {
+    clipboard: &'a dyn ClipboardProvider,
+    // `cache: launch` のコマンドの起動
+    spawner: &'a dyn Spawner,
+    query: String,
+    // after のコマンドに渡す本体の終了コードと実行時間
+    exit: Option<(Option<i32>, Duration)>,
+    // false の場合、コマンド出力の変数と ${clipboard} を評価しない
+    evaluate: bool,
+}
+
+impl<'a> LaunchContext<'a> {
+    pub fn new(clipboard: &'a dyn ClipboardProvider, spawner: &'a dyn Spawner) -> Self {
+        Self {
+            clipboard,
+            spawner,
+            query: String::new(),
+            exit: None,
+            evaluate: true,
+        }
+    }
+
+    // コマンド出力の変数を `<exec: argv>`、${clipboard} を `<clipboard>` のまま表示する
+    pub fn placeholders(mut self) -> Self {
+        self.evaluate = false;
+        self
+    }
+
+    pub fn with_query(mut self, query: &str) -> Self {
+        self.query = query.to_string();
+        self
+    }
+
+    pub fn with_exit(mut self, exit_code: Option<i32>, duration: Duration) -> Self {
+        self.exit = Some((exit_code, duration));
+        self
+    }
+
+    // 組み込み変数を解決してコマンドを最終的な形にする
+    // 参照されている変数だけを解決する（不要なクリップボード読み取りなどを避ける）
+    pub fn resolve(&self, command: &CommandSpec) -> CommandSpec {
+        let mut env = EnvVars::new();
+        for name in command.referenced_vars() {
+            if env.contains_key(name) {
+                continue;
+            }
+            let value = if let Some(exec) = command.launch_vars().get(name) {
+                if !self.evaluate {
+                    env.insert(
+                        name.to_string(),
+                        EnvValue::Single(format!("<exec: {}>", exec.argv().join(" "))),
+                    );
+                    continue;
+                }
+                match runner::capture_stdout(self.spawner, exec.argv(), exec.timeout()) {
+                    Ok(out) => Some(out),
+                    Err(e) => {
+                        error!("変数 {name} のコマンド実行に失敗しました: {e:?}");
+                        None
+                    }
+                }
+            } else if is_builtin_var(name) {
+                self.resolve_var(name)
+            } else {
+                None
+            };
+            if let Some(value) = value {
+                env.insert(name.to_string(), EnvValue::Single(value));
+            }
+        }
+        let vars = Vars::new(env, default_list_separator());
+        command.expand_launch_vars(&vars)
+    }
+
+    fn resolve_var(&self, name: &str) -> Option<String> {
+        let (base, param) = match name.split_once(':') {
+            Some((base, param)) => (base, Some(param)),
+            None => (name, None),
+        };
+        match base {
+            "clipboard" if !self.evaluate => Some("<clipboard>".to_string()),
+            "clipboard" => match self.clipboard.get_text() {
+                Ok(text) => Some(text),
+                Err(e) => {
+                    warn!("クリップボードを取得できません: {e:?}");
+                    None
+                }
+            },
+            // 入力がない場合は空文字（条件付き引数では未設定として扱われる）
+            "query" => Some(self.query.clone()),
+            "date" => format_now(param.unwrap_or("%Y-%m-%d")),
+            "time" => format_now(param.unwrap_or("%H:%M:%S")),
+            "uuid" => Some(uuid::Uuid::new_v4().to_string()),
+            "hostname" => hostname(),
+            "launcher.cwd" => std::env::current_dir()
+                .ok()
+                .map(|p| p.to_string_lossy().into_owned()),
+            "exit_code" => self
+                .exit
+                .map(|(code, _)| code.map(|c| c.to_string()).unwrap_or_default()),
+            "duration_ms" => self
+                .exit
+                .map(|(_, duration)| duration.as_millis().to_string()),
+            _ => None,
+        }
+    }
+}
+
+fn format_now(format: &str) -> Option<String> {
+    let mut out = String::new();
+    // 不正な書式の場合は to_string だと panic するため write! で受ける
+    if write!(out, "{}", chrono::Local::now().format(format)).is_err() {
+        warn!("日付の書式が不正です: {format}");
+        return None;
+    }
+    Some(out)
+}
+
+fn hostname() -> Option<String> {
+    if let Ok(name) = std::env::var("COMPUTERNAME").or_else(|_| std::env::var("HOSTNAME")) {
+        return Some(name);
+    }
+    std::fs::read_to_string("/etc/hostname")
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clipboard::MemoryClipboard;
+    use crate::model::commands::Commands;
+    use crate::spawner::RecordingSpawner;
+
+    fn spec(yaml: &str) -> CommandSpec {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn builtin_vars_are_resolved_at_launch() {
+        let clipboard = MemoryClipboard::with_text("TICKET-123");
+        let spawner = RecordingSpawner::default();
+        let ctx = LaunchContext::new(&clipboard, &spawner);
+        let cmd = spec(
+            r#"
+name: "open-ticket"
+program: "browser"
+args:
+  - "https://example.com/browse/${clipboard|trim}"
+  - "$uuid"
+  - "${date:%Y}"
+"#,
+        );
+
+        let resolved = ctx.resolve(&cmd);
+        let args = resolved.args();
+        assert_eq!(args[0], "https://example.com/browse/TICKET-123");
+        assert_eq!(args[1].len(), 36);
+        assert_eq!(args[2].len(), 4);
+    }
+
+    #[test]
+    fn stdin_template_is_resolved_with_query_and_clipboard() {
+        let clipboard = MemoryClipboard::with_text("{\"a\":1}");
+        let spawner = RecordingSpawner::default();
+        let ctx = LaunchContext::new(&clipboard, &spawner).with_query("--indent 2");
+        let cmd = spec(
+            r#"
+name: "fmt-json"
+program: "jq"
+args: [{ arg: "$query", if: query }]
+stdin: "${clipboard}\n"
+"#,
+        );
+
+        let resolved = ctx.resolve(&cmd);
+        assert_eq!(resolved.stdin(), Some("{\"a\":1}\n"));
+        assert_eq!(resolved.args(), vec!["--indent 2"]);
+    }
+
+    #[test]
+    fn conditional_arg_on_empty_clipboard_is_dropped() {
+        let clipboard = MemoryClipboard::default();
+        let spawner = RecordingSpawner::default();
+        let ctx = LaunchContext::new(&clipboard, &spawner);
+        let cmd = spec(
+            r#"
+name: "search"
+program: "browser"
+args:
+  - { arg: "--query=${clipboard}", optional: true }
+  - "--new-window"
+"#,
+        );
+
+        // 読み込み時の置換では判定されずに残る
+        let cmds = Commands::new(vec![cmd]).expand_vars(&Vars::default());
+        let cmd = cmds.find_by_name("search").unwrap();
+        assert_eq!(cmd.args().len(), 2);
+
+        let resolved = ctx.resolve(cmd);
+        assert_eq!(resolved.args(), vec!["--new-window"]);
+    }
+
+    #[test]
+    fn env_condition_is_decided_at_load_for_launch_time_arg() {
+        let clipboard = MemoryClipboard::with_text("TICKET-1");
+        let spawner = RecordingSpawner::default();
+        let ctx = LaunchContext::new(&clipboard, &spawner).with_query("rust");
+        let cmd = spec(
+            r#"
+name: "search"
+program: "curl"
+args:
+  - { arg: "--q=${query}", if: proxy }
+  - { arg: "--ticket=${clipboard}", if: debug }
+"#,
+        );
+        let mut env = EnvVars::new();
+        env.insert("proxy".into(), EnvValue::Single("http://proxy:8080".into()));
+        let cmds = Commands::new(vec![cmd]).expand_vars(&Vars::new(env, " ".into()));
+
+        let resolved = ctx.resolve(cmds.find_by_name("search").unwrap());
+        assert_eq!(resolved.args(), vec!["--q=rust"]);
+    }
+
+    #[test]
+    fn placeholders_do_not_run_commands_or_read_clipboard() {
+        let clipboard = MemoryClipboard::with_text("secret");
+        let spawner = RecordingSpawner::default();
+        let ctx = LaunchContext::new(&clipboard, &spawner)
+            .with_query("q")
+            .placeholders();
+        let cmd = spec(
+            r#"
+name: "open-branch"
+program: "browser"
+args: ["${branch}", "${clipboard}", "${query}"]
+"#,
+        );
+        let exec = serde_yaml::from_str(
+            r#"branch: { exec: ["git", "branch", "--show-current"], cache: launch }"#,
+        )
+        .unwrap();
+        let cmds = Commands::new(vec![cmd]).expand_vars(&Vars::default().with_exec_vars(exec));
+
+        let resolved = ctx.resolve(cmds.find_by_name("open-branch").unwrap());
+        assert_eq!(
+            resolved.args(),
+            vec!["<exec: git branch --show-current>", "<clipboard>", "q"]
+        );
+        assert!(spawner.argvs().is_empty());
+    }
+}
@@ -0,0 +1,338 @@
+use anyhow::bail;
+
+use crate::spawner::{ChildProcess, SpawnRequest, Spawner};
+
+// 管理者権限での実行方法
+// 起動する argv を昇格用の argv に変換し、変換した argv を起動する
+pub trait ElevationStrategy: Send + Sync {
+    fn wrap(&self, argv: Vec<String>) -> anyhow::Result<Vec<String>>;
+    // 変換済みの argv を起動する。OS の API で昇格する場合は Spawner を使わない
+    fn spawn(
+        &self,
+        spawner: &dyn Spawner,
+        request: SpawnRequest,
+    ) -> anyhow::Result<Box<dyn ChildProcess>> {
+        spawner.spawn(request)
+    }
+    // ユーザーが昇格を拒否した場合の終了コード
+    fn declined_exit_code(&self) -> Option<i32>;
+    // パスワードの入力に端末が必要か
+    fn needs_terminal(&self) -> bool {
+        false
+    }
+}
+
+// 昇格の同意画面で拒否された
+// 起動前に拒否がわかる場合（Windows）は、このエラーで起動に失敗する
+#[derive(Debug)]
+pub struct Declined;
+
+impl std::fmt::Display for Declined {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("管理者権限への昇格が拒否されました")
+    }
+}
+
+impl std::error::Error for Declined {}
+
+// 起動の失敗が昇格の拒否によるものか
+pub fn is_declined(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<Declined>().is_some()
+}
+
+// OS 標準の昇格方法
+pub fn system_strategy() -> Box<dyn ElevationStrategy> {
+    #[cfg(windows)]
+    {
+        Box::new(RunAs)
+    }
+    #[cfg(not(windows))]
+    {
+        if crate::path_lookup::find_program("pkexec").is_some() {
+            Box::new(Pkexec)
+        } else {
+            Box::new(Sudo)
+        }
+    }
+}
+
+// UAC の同意画面を表示して起動する
+// ShellExecuteEx の runas で起動したプロセスのハンドルを受け取り、終了を待つ
+// argv は変換しない（起動したプロセスは昇格したコマンドそのもの）
+#[cfg(windows)]
+pub struct RunAs;
+
+#[cfg(windows)]
+impl ElevationStrategy for RunAs {
+    fn wrap(&self, argv: Vec<String>) -> anyhow::Result<Vec<String>> {
+        if argv.is_empty() {
+            bail!("実行するコマンドが空です");
+        }
+        Ok(argv)
+    }
+
+    // 同意画面の応答まで戻らない
+    fn spawn(
+        &self,
+        _spawner: &dyn Spawner,
+        request: SpawnRequest,
+    ) -> anyhow::Result<Box<dyn ChildProcess>> {
+        use crate::spawner::Redirect;
+        use windows_sys::Win32::Foundation::{ERROR_CANCELLED, GetLastError};
+        use windows_sys::Win32::System::Threading::GetProcessId;
+        use windows_sys::Win32::UI::Shell::{
+            SEE_MASK_FLAG_NO_UI, SEE_MASK_NOASYNC, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW,
+            ShellExecuteExW,
+        };
+        use windows_sys::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+
+        let Some((program, args)) = request.argv.split_first() else {
+            bail!("実行するコマンドが空です");
+        };
+        if [&request.stdin, &request.stdout, &request.stderr]
+            .iter()
+            .any(|r| !matches!(r, Redirect::Inherit))
+        {
+            log::warn!("管理者として実行するプロセスには標準入出力を接続できません: {program}");
+        }
+        let line: Vec<String> = args.iter().map(|a| windows_quote(a)).collect();
+        let verb = wide("runas");
+        let file = wide(program);
+        let parameters = wide(&line.join(" "));
+        // SAFETY: 全体をゼロで初期化してから必要なフィールドを設定する
+        let mut info: SHELLEXECUTEINFOW = unsafe { std::mem::zeroed() };
+        info.cbSize = std::mem::size_of::<SHELLEXECUTEINFOW>() as u32;
+        info.fMask = SEE_MASK_NOCLOSEPROCESS | SEE_MASK_NOASYNC | SEE_MASK_FLAG_NO_UI;
+        info.lpVerb = verb.as_ptr();
+        info.lpFile = file.as_ptr();
+        info.lpParameters = parameters.as_ptr();
+        info.nShow = SW_SHOWNORMAL;
+        // SAFETY: 文字列のバッファは呼び出しの間生きている
+        if unsafe { ShellExecuteExW(&mut info) } == 0 {
+            let code = unsafe { GetLastError() };
+            if code == ERROR_CANCELLED {
+                return Err(Declined.into());
+            }
+            bail!("管理者として起動できません: {program} (error={code})");
+        }
+        if info.hProcess == 0 {
+            bail!("起動したプロセスのハンドルを取得できません: {program}");
+        }
+        let pid = unsafe { GetProcessId(info.hProcess) };
+        Ok(Box::new(ElevatedChild {
+            handle: info.hProcess,
+            pid,
+        }))
+    }
+
+    // 起動前に拒否がわかるため、終了コードでは判定しない
+    fn declined_exit_code(&self) -> Option<i32> {
+        None
+    }
+}
+
+// ShellExecuteEx で起動したプロセス
+#[cfg(windows)]
+struct ElevatedChild {
+    handle: windows_sys::Win32::Foundation::HANDLE,
+    pid: u32,
+}
+
+#[cfg(windows)]
+impl ElevatedChild {
+    fn wait_for(&self, millis: u32) -> std::io::Result<Option<std::process::ExitStatus>> {
+        use std::os::windows::process::ExitStatusExt;
+        use windows_sys::Win32::Foundation::{WAIT_OBJECT_0, WAIT_TIMEOUT};
+        use windows_sys::Win32::System::Threading::{GetExitCodeProcess, WaitForSingleObject};
+
+        // SAFETY: handle は Drop まで有効
+        match unsafe { WaitForSingleObject(self.handle, millis) } {
+            WAIT_OBJECT_0 => {
+                let mut code = 0u32;
+                if unsafe { GetExitCodeProcess(self.handle, &mut code) } == 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(Some(std::process::ExitStatus::from_raw(code)))
+            }
+            WAIT_TIMEOUT => Ok(None),
+            _ => Err(std::io::Error::last_os_error()),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl ChildProcess for ElevatedChild {
+    fn id(&self) -> u32 {
+        self.pid
+    }
+    fn try_wait(&mut self) -> std::io::Result<Option<std::process::ExitStatus>> {
+        self.wait_for(0)
+    }
+    fn wait(&mut self) -> std::io::Result<std::process::ExitStatus> {
+        use windows_sys::Win32::System::Threading::INFINITE;
+        self.wait_for(INFINITE)?
+            .ok_or_else(|| std::io::Error::other("プロセスの終了を待てません"))
+    }
+    fn kill(&mut self) -> std::io::Result<()> {
+        use windows_sys::Win32::System::Threading::TerminateProcess;
+        // SAFETY: handle は Drop まで有効
+        if unsafe { TerminateProcess(self.handle, 1) } == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    fn kill_tree(&mut self) -> anyhow::Result<()> {
+        crate::spawner::kill_tree_or_self(self)
+    }
+    fn take_stdin(&mut self) -> Option<Box<dyn std::io::Write + Send>> {
+        None
+    }
+    fn take_stdout(&mut self) -> Option<Box<dyn std::io::Read + Send>> {
+        None
+    }
+    fn take_stderr(&mut self) -> Option<Box<dyn std::io::Read + Send>> {
+        None
+    }
+}
+
+#[cfg(windows)]
+impl Drop for ElevatedChild {
+    fn drop(&mut self) {
+        // SAFETY: ShellExecuteEx が返したハンドルを一度だけ閉じる
+        unsafe { windows_sys::Win32::Foundation::CloseHandle(self.handle) };
+    }
+}
+
+#[cfg(windows)]
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+// Windows のコマンドライン引数の引用（CommandLineToArgvW の規則）
+#[cfg(windows)]
+fn windows_quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+// polkit の認証ダイアログを表示して起動する
+// pkexec は環境変数と作業ディレクトリを引き継がない
+#[cfg(not(windows))]
+pub struct Pkexec;
+
+#[cfg(not(windows))]
+impl ElevationStrategy for Pkexec {
+    fn wrap(&self, argv: Vec<String>) -> anyhow::Result<Vec<String>> {
+        if argv.is_empty() {
+            bail!("実行するコマンドが空です");
+        }
+        Ok(std::iter::once("pkexec".to_string()).chain(argv).collect())
+    }
+
+    // 認証ダイアログを閉じた、または認証に失敗した
+    fn declined_exit_code(&self) -> Option<i32> {
+        Some(126)
+    }
+}
+
+// パスワードの入力に端末が必要なため、terminal: true と組み合わせて使う
+// 端末がない場合は起動前にエラーにする（needs_terminal）
+#[cfg(not(windows))]
+pub struct Sudo;
+
+#[cfg(not(windows))]
+impl ElevationStrategy for Sudo {
+    fn wrap(&self, argv: Vec<String>) -> anyhow::Result<Vec<String>> {
+        if argv.is_empty() {
+            bail!("実行するコマンドが空です");
+        }
+        Ok(["sudo", "--"]
+            .into_iter()
+            .map(str::to_string)
+            .chain(argv)
+            .collect())
+    }
+
+    // 認証失敗とコマンド自体の失敗を終了コードで区別できない
+    fn declined_exit_code(&self) -> Option<i32> {
+        None
+    }
+
+    fn needs_terminal(&self) -> bool {
+        true
+    }
+}
+
+// テスト用。変換を記録し、先頭に "elevate" を付ける
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockElevation {
+    pub calls: std::sync::Mutex<Vec<Vec<String>>>,
+}
+
+#[cfg(test)]
+impl ElevationStrategy for MockElevation {
+    fn wrap(&self, argv: Vec<String>) -> anyhow::Result<Vec<String>> {
+        self.calls.lock().unwrap().push(argv.clone());
+        Ok(std::iter::once("elevate".to_string()).chain(argv).collect())
+    }
+
+    fn declined_exit_code(&self) -> Option<i32> {
+        Some(99)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(windows))]
+    #[test]
+    fn linux_strategies_prefix_argv() {
+        let argv = vec!["nmcli".to_string(), "con".to_string(), "up".to_string()];
+        assert_eq!(
+            Pkexec.wrap(argv.clone()).unwrap(),
+            ["pkexec", "nmcli", "con", "up"]
+        );
+        assert_eq!(
+            Sudo.wrap(argv).unwrap(),
+            ["sudo", "--", "nmcli", "con", "up"]
+        );
+        assert!(Pkexec.wrap(vec![]).is_err());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn runas_quotes_arguments_for_shell_execute() {
+        assert_eq!(windows_quote("name=Ethernet"), "name=Ethernet");
+        assert_eq!(windows_quote("Wi-Fi 2"), r#""Wi-Fi 2""#);
+        assert_eq!(windows_quote(r#"C:\dir\ "x""#), r#""C:\dir\ \"x\"""#);
+        assert_eq!(windows_quote(""), r#""""#);
+
+        // argv は変換せず、ShellExecuteEx で昇格する
+        let argv = vec!["netsh".to_string(), "a b".to_string()];
+        assert_eq!(RunAs.wrap(argv.clone()).unwrap(), argv);
+        assert!(RunAs.wrap(vec![]).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use crate::model::commands::{CommandSpec, Lifetime, ProgramStatus, VarOrigin};
+use crate::model::restart::RestartOn;
+use crate::runner;
+use crate::terminal::TerminalConfig;
+
+// 起動せずに、resolve 済みのコマンドがどう起動されるかを説明する
+// CLI の `explain` / `run --dry-run` と UI のプレビューで使う
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    pub name: String,
+    lines: Vec<String>,
+}
+
+impl Explanation {
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+}
+
+pub fn explain(command: &CommandSpec, terminal: &TerminalConfig) -> Explanation {
+    let mut lines = Vec::new();
+    lines.push(format!("コマンド: {}", command.name()));
+    match command.source() {
+        Some(path) => lines.push(format!("定義: {}", path.display())),
+        None => lines.push("定義: 不明".to_string()),
+    }
+    lines.push(format!("プログラム: {}", command.program_summary()));
+
+    match runner::planned_argv(command, terminal) {
+        Ok(argv) => {
+            lines.push("argv:".to_string());
+            for (i, arg) in argv.iter().enumerate() {
+                lines.push(format!("  [{i}] {arg}"));
+            }
+        }
+        Err(e) => lines.push(format!("argv: 組み立てられません: {e:#}")),
+    }
+    if command.elevate() {
+        let how = if cfg!(windows) {
+            "UAC の同意画面で昇格して起動する"
+        } else {
+            "argv は昇格用のコマンドを含む"
+        };
+        lines.push(format!("管理者権限: 昇格して実行する（{how}）"));
+    }
+    if command.terminal() {
+        lines.push("端末: 端末を開いて実行する".to_string());
+    }
+    if let Some(restart) = command.restart() {
+        let on = match restart.on() {
+            RestartOn::Failure => "失敗した場合",
+            RestartOn::Always => "終了した場合は常に",
+        };
+        lines.push(format!(
+            "起動し直し: {on}、最大 {} 回 (常駐アプリから起動した場合)",
+            restart.max_retries()
+        ));
+    }
+    if !command.tags().is_empty() {
+        lines.push(format!("タグ: {}", command.tags().join(", ")));
+    }
+    if command.lifetime() == Lifetime::Tied {
+        lines.push(
+            "寿命: 常駐アプリの終了時に終了させる (CLI から起動した場合は切り離す)".to_string(),
+        );
+        if command.elevate() {
+            lines.push(
+                "  管理者として実行したプロセスは終了させられない (elevate と併用しても対象外)"
+                    .to_string(),
+            );
+        }
+    }
+
+    // 作業ディレクトリと環境変数は指定できないため、ランチャーのものを引き継ぐ
+    let cwd = std::env::current_dir()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| "不明".to_string());
+    lines.push(format!("作業ディレクトリ: {cwd} (ランチャーから引き継ぐ)"));
+    lines.push("環境変数: 変更なし (ランチャーの環境を引き継ぐ)".to_string());
+    if let Some(stdin) = command.stdin() {
+        lines.push(format!("標準入力: {stdin:?}"));
+    }
+
+    for (label, hooks) in [
+        ("起動前に実行", command.before_hooks()),
+        ("終了後に実行", command.after_hooks()),
+    ] {
+        if hooks.is_empty() {
+            continue;
+        }
+        lines.push(format!("{label}:"));
+        for hook in hooks {
+            lines.push(format!(
+                "  {}: {}",
+                hook.name(),
+                runner::resolved_argv(hook).join(" ")
+            ));
+        }
+    }
+
+    if command.substitutions().is_empty() {
+        lines.push("置換: なし".to_string());
+    } else {
+        lines.push("置換:".to_string());
+        for s in command.substitutions() {
+            let origin = match s.origin {
+                VarOrigin::Env => "env.yaml",
+                VarOrigin::LaunchExec => "env.yaml のコマンド出力 (起動時に評価)",
+                VarOrigin::Builtin => "組み込み変数",
+            };
+            lines.push(format!("  {} = {:?} ({origin})", s.name, s.value));
+        }
+    }
+    if let ProgramStatus::Unavailable(_) = command.program_status() {
+        lines.push("このコマンドは起動できません".to_string());
+    }
+
+    Explanation {
+        name: command.name().to_string(),
+        lines,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clipboard::MemoryClipboard;
+    use crate::context::LaunchContext;
+    use crate::model::commands::Commands;
+    use crate::model::vars::{EnvValue, EnvVars, Vars};
+    use std::path::PathBuf;
+
+    fn setting_path() -> PathBuf {
+        PathBuf::from("/home/user/.config/command-launcher/setting.yaml")
+    }
+
+    #[test]
+    fn explains_argv_and_variable_origins() {
+        let commands = Commands::new(
+            serde_yaml::from_str(
+                r#"
+- name: "search"
+  program: "$browser"
+  args: ["https://example.com/?q=${query|urlencode}", "--profile=${clipboard}"]
+"#,
+            )
+            .unwrap(),
+        )
+        .with_source(&setting_path());
+        let mut env = EnvVars::new();
+        env.insert("browser".into(), EnvValue::Single("firefox".into()));
+        let commands = commands.expand_vars(&Vars::new(env, " ".into()));
+
+        let clipboard = MemoryClipboard::with_text("work");
+        let spawner = crate::spawner::RecordingSpawner::default();
+        let command = LaunchContext::new(&clipboard, &spawner)
+            .with_query("rust lang")
+            .resolve(commands.find_by_name("search").unwrap());
+        let explanation = explain(&command, &TerminalConfig::default());
+        let lines = explanation.lines();
+
+        assert!(lines.contains(&format!("定義: {}", setting_path().display())));
+        assert!(lines.contains(&"  [0] firefox".to_string()));
+        assert!(lines.contains(&"  [1] https://example.com/?q=rust%20lang".to_string()));
+        assert!(lines.contains(&"  [2] --profile=work".to_string()));
+        assert!(lines.contains(&"  browser = \"firefox\" (env.yaml)".to_string()));
+        assert!(lines.contains(&"  query = \"rust lang\" (組み込み変数)".to_string()));
+        assert!(lines.contains(&"  clipboard = \"work\" (組み込み変数)".to_string()));
+    }
+}
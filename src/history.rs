@@ -0,0 +1,512 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, bail};
+use chrono::{DateTime, Local, NaiveDate};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::app::processes::{ProcessInfo, ProcessState};
+use crate::config;
+use crate::model::ranking::Usage;
+
+// 起動元
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LaunchSource {
+    Ui,
+    // ホットキーで開いたランチャーからの起動
+    Hotkey,
+    // タスクトレイから開いたランチャーからの起動
+    Tray,
+    Cli,
+    // 常駐アプリの定期実行
+    Schedule,
+    // 常駐アプリの起動時・終了時のコマンド
+    Hook,
+    // 常駐アプリが終了したプロセスを起動し直した（restart）
+    Restart,
+}
+
+impl LaunchSource {
+    // 利用者がランチャーや CLI から選んで起動したか
+    pub fn is_user_initiated(self) -> bool {
+        !matches!(
+            self,
+            LaunchSource::Schedule | LaunchSource::Hook | LaunchSource::Restart
+        )
+    }
+}
+
+// 実行結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    // 終了コード 0
+    Success,
+    // 0 以外の終了コード
+    Failed,
+    // シグナルなどで終了（終了コードなし）
+    Signaled,
+    // timeout により終了させた
+    TimedOut,
+    // 管理者権限への昇格を拒否された
+    Declined,
+    // 起動できなかった
+    SpawnError,
+    // 起動した時点の記録。終了すると同じ起動の終了の記録で置き換わる
+    // （終了の記録がないものは、ランチャーや CLI が終了を記録できなかったもの）
+    #[serde(alias = "running")]
+    Started,
+}
+
+impl RunStatus {
+    fn parse(s: &str) -> anyhow::Result<Self> {
+        serde_json::from_value(serde_json::Value::String(s.to_string()))
+            .with_context(|| format!("不明なステータスです: {s}"))
+    }
+}
+
+// 実行履歴の 1 件（history.jsonl の 1 行）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    pub timestamp: DateTime<Local>,
+    pub command: String,
+    pub argv: Vec<String>,
+    pub source: LaunchSource,
+    pub pid: Option<u32>,
+    pub status: RunStatus,
+    pub exit_code: Option<i32>,
+    pub duration_ms: Option<u64>,
+}
+
+impl HistoryRecord {
+    pub fn spawned(command: &str, argv: Vec<String>, source: LaunchSource, pid: u32) -> Self {
+        Self {
+            timestamp: Local::now(),
+            command: command.to_string(),
+            argv,
+            source,
+            pid: Some(pid),
+            status: RunStatus::Started,
+            exit_code: None,
+            duration_ms: None,
+        }
+    }
+
+    pub fn spawn_error(command: &str, argv: Vec<String>, source: LaunchSource) -> Self {
+        Self {
+            timestamp: Local::now(),
+            command: command.to_string(),
+            argv,
+            source,
+            pid: None,
+            status: RunStatus::SpawnError,
+            exit_code: None,
+            duration_ms: None,
+        }
+    }
+
+    // 起動前に昇格を拒否された場合（spawn_error を declined にする）
+    pub fn declined(mut self) -> Self {
+        self.status = RunStatus::Declined;
+        self
+    }
+
+    // 終了を待った場合の結果を反映する（timeout で終了させた場合は exit_code なし）
+    pub fn with_exit(
+        mut self,
+        exit_code: Option<i32>,
+        timed_out: bool,
+        duration: Duration,
+    ) -> Self {
+        self.status = match exit_code {
+            _ if timed_out => RunStatus::TimedOut,
+            Some(0) => RunStatus::Success,
+            Some(_) => RunStatus::Failed,
+            None => RunStatus::Signaled,
+        };
+        self.exit_code = exit_code;
+        self.duration_ms = u64::try_from(duration.as_millis()).ok();
+        self
+    }
+
+    // 起動の記録と終了の記録の対応付けに使う（同じ起動は起動時刻と pid が同じ）
+    fn run_key(&self) -> Option<(String, u32, DateTime<Local>)> {
+        self.pid
+            .map(|pid| (self.command.clone(), pid, self.timestamp))
+    }
+
+    // 管理中のプロセスの状態から作成する
+    pub fn from_process(process: &ProcessInfo) -> Self {
+        let (status, exit_code) = match process.state {
+            ProcessState::Running => (RunStatus::Started, None),
+            ProcessState::Exited(code) if process.elevation_declined => (RunStatus::Declined, code),
+            ProcessState::Exited(code) if process.timed_out => (RunStatus::TimedOut, code),
+            ProcessState::Exited(Some(0)) => (RunStatus::Success, Some(0)),
+            ProcessState::Exited(Some(code)) => (RunStatus::Failed, Some(code)),
+            ProcessState::Exited(None) => (RunStatus::Signaled, None),
+        };
+        Self {
+            timestamp: process.started_at,
+            command: process.name.clone(),
+            argv: process.argv.clone(),
+            source: process.source,
+            pid: Some(process.pid),
+            status,
+            exit_code,
+            duration_ms: process
+                .runtime()
+                .and_then(|d| u64::try_from(d.num_milliseconds()).ok()),
+        }
+    }
+
+    // 一覧表示用の 1 行
+    pub fn display_line(&self) -> String {
+        let status = match (self.status, self.exit_code) {
+            (RunStatus::Failed, Some(code)) => format!("failed({code})"),
+            (status, _) => serde_json::to_value(status)
+                .ok()
+                .and_then(|v| v.as_str().map(str::to_string))
+                .unwrap_or_default(),
+        };
+        let duration = self
+            .duration_ms
+            .map(|ms| format!("{:.1}s", ms as f64 / 1000.0))
+            .unwrap_or_else(|| "-".to_string());
+        let source = match self.source {
+            LaunchSource::Ui => "ui",
+            LaunchSource::Hotkey => "hotkey",
+            LaunchSource::Tray => "tray",
+            LaunchSource::Cli => "cli",
+            LaunchSource::Schedule => "sched",
+            LaunchSource::Hook => "hook",
+            LaunchSource::Restart => "retry",
+        };
+        format!(
+            "{}  {:<5} {:<20} {:<12} {:>8}  {}",
+            self.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            source,
+            self.command,
+            status,
+            duration,
+            self.argv.join(" ")
+        )
+    }
+}
+
+// 履歴に 1 件追記する
+pub fn append(record: &HistoryRecord) -> anyhow::Result<()> {
+    append_to(&config::history_path()?, record)
+}
+
+// 履歴の追記に失敗しても起動処理自体は続けるため、ログに残すだけにする
+pub fn append_or_warn(record: &HistoryRecord) {
+    if let Err(e) = append(record) {
+        warn!("実行履歴を記録できません: {e:?}");
+    }
+}
+
+fn append_to(path: &Path, record: &HistoryRecord) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("ディレクトリを作成できません: {}", dir.display()))?;
+    }
+    let mut line = serde_json::to_string(record).context("実行履歴を JSON に変換できません")?;
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("実行履歴ファイルを開けません: {}", path.display()))?;
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("実行履歴を書き込めません: {}", path.display()))
+}
+
+// 履歴をすべて読み込む（起動の記録と終了の記録は 1 件にまとめる）。解釈できない行は読み飛ばす
+pub fn load() -> anyhow::Result<Vec<HistoryRecord>> {
+    load_from(&config::history_path()?)
+}
+
+fn load_from(path: &Path) -> anyhow::Result<Vec<HistoryRecord>> {
+    let content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("実行履歴ファイルを読み込めません: {}", path.display()));
+        }
+    };
+    let records = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(
+            |(i, line)| match serde_json::from_str::<HistoryRecord>(line) {
+                Ok(r) => Some(r),
+                Err(e) => {
+                    warn!("実行履歴の {} 行目を解釈できません: {e}", i + 1);
+                    None
+                }
+            },
+        )
+        .collect();
+    Ok(merge_runs(records))
+}
+
+// 同じ起動の記録は後から追記されたもの（終了の記録）だけを残す。順序は起動の記録の位置
+fn merge_runs(records: Vec<HistoryRecord>) -> Vec<HistoryRecord> {
+    let mut merged: Vec<HistoryRecord> = Vec::with_capacity(records.len());
+    let mut index: HashMap<(String, u32, DateTime<Local>), usize> = HashMap::new();
+    for record in records {
+        match record.run_key() {
+            Some(key) => match index.get(&key) {
+                Some(&i) => merged[i] = record,
+                None => {
+                    index.insert(key, merged.len());
+                    merged.push(record);
+                }
+            },
+            None => merged.push(record),
+        }
+    }
+    merged
+}
+
+// 候補の並び替え用の起動履歴（起動できなかったものは数えない）
+// 定期実行、起動時・終了時のコマンド、起動し直したものは利用者が選んだものではないため数えない
+pub fn usage(records: &[HistoryRecord]) -> Usage {
+    let mut usage = Usage::default();
+    for record in records
+        .iter()
+        .filter(|r| r.status != RunStatus::SpawnError && r.source.is_user_initiated())
+    {
+        usage.record(&record.command, record.timestamp);
+    }
+    usage
+}
+
+// 実行履歴を読み込めない場合は空の履歴として扱う
+pub fn load_usage() -> Usage {
+    match load() {
+        Ok(records) => usage(&records),
+        Err(e) => {
+            warn!("実行履歴を読み込めません: {e:?}");
+            Usage::default()
+        }
+    }
+}
+
+// コマンドごとの最後の定期実行の時刻（見逃した実行の判定に使う）
+pub fn last_scheduled_runs() -> HashMap<String, DateTime<Local>> {
+    match load() {
+        Ok(records) => scheduled_runs(&records),
+        Err(e) => {
+            warn!("実行履歴を読み込めません: {e:?}");
+            HashMap::new()
+        }
+    }
+}
+
+// 起動の記録は起動した時点で追記するため、終了の記録がない実行（常駐アプリの異常終了など）も数える
+fn scheduled_runs(records: &[HistoryRecord]) -> HashMap<String, DateTime<Local>> {
+    let mut last = HashMap::new();
+    for record in records
+        .iter()
+        .filter(|r| r.source == LaunchSource::Schedule)
+    {
+        last.entry(record.command.clone())
+            .and_modify(|t: &mut DateTime<Local>| *t = (*t).max(record.timestamp))
+            .or_insert(record.timestamp);
+    }
+    last
+}
+
+// `history` サブコマンドの条件
+#[derive(Debug, Default, PartialEq)]
+pub struct HistoryQuery {
+    pub command: Option<String>,
+    pub status: Option<RunStatus>,
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+    pub limit: Option<usize>,
+    pub json: bool,
+}
+
+impl HistoryQuery {
+    // `history [--command <name>] [--status <status>] [--since <YYYY-MM-DD>] [--until <YYYY-MM-DD>] [--limit <n>] [--json]`
+    pub fn parse(args: &[String]) -> anyhow::Result<Self> {
+        let mut query = HistoryQuery::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let mut value = || {
+                iter.next()
+                    .with_context(|| format!("{arg} の値が指定されていません"))
+            };
+            match arg.as_str() {
+                "--command" => query.command = Some(value()?.clone()),
+                "--status" => query.status = Some(RunStatus::parse(value()?)?),
+                "--since" => query.since = Some(parse_date(value()?)?),
+                "--until" => query.until = Some(parse_date(value()?)?),
+                "--limit" => {
+                    let v = value()?;
+                    query.limit = Some(
+                        v.parse()
+                            .with_context(|| format!("--limit の値が不正です: {v}"))?,
+                    );
+                }
+                "--json" => query.json = true,
+                other => bail!("不明なオプションです: {other}"),
+            }
+        }
+        Ok(query)
+    }
+
+    pub fn matches(&self, record: &HistoryRecord) -> bool {
+        let date = record.timestamp.date_naive();
+        self.command.as_ref().is_none_or(|c| *c == record.command)
+            && self.status.is_none_or(|s| s == record.status)
+            && self.since.is_none_or(|since| date >= since)
+            && self.until.is_none_or(|until| date <= until)
+    }
+
+    // 条件に合う履歴（起動時刻の古い順、limit 指定時は新しいものから limit 件）
+    // 終了の記録は終了時に追記されるため、ファイル内の順序は起動時刻の順とは限らない
+    pub fn apply(&self, records: Vec<HistoryRecord>) -> Vec<HistoryRecord> {
+        let mut matched: Vec<HistoryRecord> =
+            records.into_iter().filter(|r| self.matches(r)).collect();
+        matched.sort_by_key(|r| r.timestamp);
+        if let Some(limit) = self.limit {
+            let skip = matched.len().saturating_sub(limit);
+            matched.drain(..skip);
+        }
+        matched
+    }
+}
+
+fn parse_date(s: &str) -> anyhow::Result<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .with_context(|| format!("日付の書式が不正です（YYYY-MM-DD）: {s}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(command: &str, status: RunStatus, date: &str) -> HistoryRecord {
+        let timestamp = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap();
+        HistoryRecord {
+            timestamp,
+            command: command.into(),
+            argv: vec!["prog".into(), "--flag".into()],
+            source: LaunchSource::Ui,
+            pid: Some(42),
+            status,
+            exit_code: None,
+            duration_ms: Some(1500),
+        }
+    }
+
+    #[test]
+    fn append_and_load_roundtrip() {
+        let path = std::env::temp_dir().join(format!("history_test_{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let a = record("build", RunStatus::Success, "2026-01-10");
+        let b = record("deploy", RunStatus::SpawnError, "2026-01-11");
+        append_to(&path, &a).unwrap();
+        append_to(&path, &b).unwrap();
+
+        assert_eq!(load_from(&path).unwrap(), vec![a, b]);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn query_filters_by_command_status_and_date() {
+        let args: Vec<String> = ["--status", "failed", "--since", "2026-01-11", "--json"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let query = HistoryQuery::parse(&args).unwrap();
+        assert!(query.json);
+
+        let records = vec![
+            record("build", RunStatus::Failed, "2026-01-10"),
+            record("build", RunStatus::Failed, "2026-01-12"),
+            record("build", RunStatus::Success, "2026-01-12"),
+        ];
+        let matched = query.apply(records);
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].timestamp.date_naive().to_string(), "2026-01-12");
+
+        assert!(HistoryQuery::parse(&["--status".into(), "nope".into()]).is_err());
+    }
+
+    #[test]
+    fn scheduled_runs_count_started_records_without_exit() {
+        let scheduled = |command: &str, status: RunStatus, date: &str| HistoryRecord {
+            source: LaunchSource::Schedule,
+            ..record(command, status, date)
+        };
+        let records = vec![
+            scheduled("backup", RunStatus::Success, "2026-01-10"),
+            // 実行中に常駐アプリが終了し、終了の記録がない
+            scheduled("backup", RunStatus::Started, "2026-01-11"),
+            record("backup", RunStatus::Success, "2026-01-12"),
+        ];
+        let last = scheduled_runs(&records);
+        assert_eq!(last["backup"].date_naive().to_string(), "2026-01-11");
+    }
+
+    #[test]
+    fn exit_record_replaces_started_record_and_limit_uses_start_time() {
+        let path = std::env::temp_dir().join(format!("history_merge_{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let long = record("long", RunStatus::Started, "2026-01-10");
+        let short = HistoryRecord {
+            pid: Some(43),
+            ..record("short", RunStatus::Started, "2026-01-11")
+        };
+        // short が先に終わり、long の終了の記録は後から追記される
+        append_to(&path, &long).unwrap();
+        append_to(&path, &short).unwrap();
+        let short_exit = HistoryRecord {
+            status: RunStatus::Success,
+            ..short.clone()
+        };
+        append_to(&path, &short_exit).unwrap();
+        let crashed = HistoryRecord {
+            pid: Some(44),
+            ..record("crashed", RunStatus::Started, "2026-01-09")
+        };
+        append_to(&path, &crashed).unwrap();
+        append_to(&path, &record("long", RunStatus::Failed, "2026-01-10")).unwrap();
+
+        let records = load_from(&path).unwrap();
+        assert_eq!(records.len(), 3);
+        let query = HistoryQuery {
+            limit: Some(2),
+            ..HistoryQuery::default()
+        };
+        let latest: Vec<(String, RunStatus)> = query
+            .apply(records)
+            .into_iter()
+            .map(|r| (r.command, r.status))
+            .collect();
+        assert_eq!(
+            latest,
+            vec![
+                ("long".to_string(), RunStatus::Failed),
+                ("short".to_string(), RunStatus::Success)
+            ]
+        );
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -3,14 +3,26 @@
 use anyhow::Context;
 use log::LevelFilter;
 use log::{error, info};
+use std::io::Write;
 use std::sync::mpsc;
 
 mod app;
 mod model;
 mod ui;
 
+mod clipboard;
 mod config;
+mod context;
+mod elevation;
+mod explain;
+mod history;
+mod notify;
+mod path_lookup;
+mod process_control;
+mod run_log;
 mod runner;
+mod spawner;
+mod terminal;
 
 use crate::app::hotkey::Hotkey;
 use app::controller::Controller;
@@ -43,32 +55,126 @@ fn init_logger() {
 }
 
 fn start_cli() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    // CLI でコピーした内容を保持するプロセス（clipboard::set_text_for_cli が起動する）
+    #[cfg(target_os = "linux")]
+    if args.get(1).map(String::as_str) == Some(clipboard::HOLD_ARG) {
+        return clipboard::hold_from_stdin();
+    }
+
     let settings = config::load_settings()?;
 
-    let args: Vec<String> = std::env::args().collect();
     match args.get(1).map(|s| s.as_str()) {
         Some("list") => {
-            info!("commands list: {:?}", settings.commands());
+            // よく使うコマンドが先頭になるよう、実行履歴に基づいて並べる
+            let cmds = settings.commands();
+            let usage = history::load_usage();
+            let ranked = model::ranking::rank(&cmds, &usage, "", chrono::Local::now());
+            let mut out = std::io::stdout().lock();
+            for cmd in ranked {
+                writeln!(out, "{}\t{}", cmd.name(), cmd.program_summary())?;
+            }
+            return Ok(());
+        }
+        Some("validate") => {
+            // 実行ファイルが見つからないコマンドを理由とともに出力する
+            let cmds = settings.commands();
+            let mut out = std::io::stdout().lock();
+            let unavailable: Vec<_> = cmds.iter().filter(|cmd| !cmd.is_available()).collect();
+            for cmd in &unavailable {
+                writeln!(out, "{}\t{}", cmd.name(), cmd.program_summary())?;
+            }
+            if !unavailable.is_empty() {
+                anyhow::bail!("利用できないコマンドがあります: {} 件", unavailable.len());
+            }
+            writeln!(out, "すべてのコマンドが利用できます")?;
             return Ok(());
         }
         Some("run-first") => {
+            let deps = cli_deps(&settings);
             let cmds = settings.commands();
             let first = cmds.first().context("commands が空です")?;
 
-            runner::spawn_command(first)?;
-            info!("{:?}を起動しました", first.name());
-            return Ok(());
+            let (options, query) = split_query(&args[2..]);
+            if has_flag(options, "--dry-run") {
+                return print_explanation(&deps, first, &query, has_flag(options, "--eval"));
+            }
+            return run_from_cli(&deps, first, has_yes_flag(options), &query);
         }
         Some("run") => {
-            let name = args.get(2).context("使い方: command-launcher run <name>")?;
+            // オプションはコマンド名の前にも書ける（`run --dry-run <name>`）
+            let (options, query) = split_query(&args[2..]);
+            let name = command_name(options).context(
+                "使い方: command-launcher run [--yes] [--dry-run [--eval]] <name> [-- <query>...]",
+            )?;
 
+            let deps = cli_deps(&settings);
             let cmds = settings.commands();
             let cmd = cmds
                 .find_by_name(name)
                 .with_context(|| format!("指定されたコマンドが見つかりません: {name}"))?;
 
-            runner::spawn_command(cmd)?;
-            info!("{:?}を起動しました", cmd.name());
+            if has_flag(options, "--dry-run") {
+                return print_explanation(&deps, cmd, &query, has_flag(options, "--eval"));
+            }
+            return run_from_cli(&deps, cmd, has_yes_flag(options), &query);
+        }
+        Some("explain") => {
+            // 起動せずに、最終的な argv と置換の内訳を出力する
+            let (options, query) = split_query(&args[2..]);
+            let name = command_name(options)
+                .context("使い方: command-launcher explain [--eval] <name> [-- <query>...]")?;
+
+            let deps = cli_deps(&settings);
+            let cmds = settings.commands();
+            let cmd = cmds
+                .find_by_name(name)
+                .with_context(|| format!("指定されたコマンドが見つかりません: {name}"))?;
+            return print_explanation(&deps, cmd, &query, has_flag(options, "--eval"));
+        }
+        Some("schedule") => {
+            // 定期実行するコマンドと次の予定時刻を出力する
+            if args.get(2).map(String::as_str) != Some("list") {
+                anyhow::bail!("使い方: command-launcher schedule list");
+            }
+            let scheduler = app::scheduler::Scheduler::new(
+                &settings.commands(),
+                &history::last_scheduled_runs(),
+                Box::new(app::scheduler::SystemClock),
+            );
+            let mut out = std::io::stdout().lock();
+            for (command, schedule, next) in scheduler.entries() {
+                let next = next
+                    .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_else(|| "-".to_string());
+                writeln!(
+                    out,
+                    "{}\t{}\t次回: {next}",
+                    command.name(),
+                    schedule.trigger()
+                )?;
+            }
+            return Ok(());
+        }
+        Some("log") => {
+            // 最新のログファイルのパスを出力する（コマンド名を指定した場合はそのコマンドのログ）
+            let name = args.get(2).map(|s| s.as_str());
+            let path = run_log::latest(name)?.context("ログファイルがありません")?;
+            writeln!(std::io::stdout(), "{}", path.display())?;
+            return Ok(());
+        }
+        Some("history") => {
+            // 実行履歴を表示する
+            let query = history::HistoryQuery::parse(&args[2..])?;
+            let records = query.apply(history::load()?);
+            let mut out = std::io::stdout().lock();
+            for record in &records {
+                if query.json {
+                    writeln!(out, "{}", serde_json::to_string(record)?)?;
+                } else {
+                    writeln!(out, "{}", record.display_line())?;
+                }
+            }
             return Ok(());
         }
         _ => {
@@ -78,6 +184,192 @@ fn start_cli() -> anyhow::Result<()> {
     Ok(())
 }
 
+// CLI から起動する際の依存（lifetime: tied のプロセスも切り離して起動する）
+fn cli_deps(settings: &config::Settings) -> runner::LaunchDeps {
+    runner::LaunchDeps::system(
+        std::sync::Arc::new(spawner::OsSpawner::default()),
+        settings.terminal().clone(),
+    )
+}
+
+// CLI から起動する。起動の記録を追記し、終了を待って終了の記録も追記する
+fn run_from_cli(
+    deps: &runner::LaunchDeps,
+    command: &model::commands::CommandSpec,
+    yes: bool,
+    query: &str,
+) -> anyhow::Result<()> {
+    let command = &runner::resolve(deps, command, query);
+    if command.needs_confirmation() && !yes && !confirm_on_terminal(command)? {
+        info!("{:?}の実行をキャンセルしました", command.name());
+        return Ok(());
+    }
+    match runner::spawn_command(deps, command) {
+        Ok(mut spawned) => {
+            let record = history::HistoryRecord::spawned(
+                command.name(),
+                spawned.argv.clone(),
+                history::LaunchSource::Cli,
+                spawned.child.id(),
+            );
+            info!("{:?}を起動しました", command.name());
+            history::append_or_warn(&record);
+            wait_cli_command(deps, &mut spawned, record)
+        }
+        Err(e) => {
+            let record = history::HistoryRecord::spawn_error(
+                command.name(),
+                runner::resolved_argv(command),
+                history::LaunchSource::Cli,
+            );
+            history::append_or_warn(&if elevation::is_declined(&e) {
+                record.declined()
+            } else {
+                record
+            });
+            Err(e)
+        }
+    }
+}
+
+// 終了を待って終了コードと実行時間を記録する
+// output: clipboard / show は出力をコピーまたは標準出力に出力し、after のコマンドを実行する
+fn wait_cli_command(
+    deps: &runner::LaunchDeps,
+    spawned: &mut runner::Spawned,
+    record: history::HistoryRecord,
+) -> anyhow::Result<()> {
+    let started = std::time::Instant::now();
+    let status = match spawned.timeout {
+        Some(timeout) => runner::wait_timeout(spawned.child.as_mut(), timeout)?,
+        None => Some(spawned.child.wait().context("プロセスの終了を待てません")?),
+    };
+    let Some(status) = status else {
+        // 常駐アプリと同じく終了を要求し、猶予を過ぎても終了しなければ子孫プロセスごと強制終了する
+        let pid = spawned.child.id();
+        if let Err(e) = process_control::terminate(pid) {
+            log::warn!("終了を要求できません: pid={pid}: {e:?}");
+        }
+        let grace = app::processes::TERMINATE_GRACE;
+        if !matches!(
+            runner::wait_timeout(spawned.child.as_mut(), grace),
+            Ok(Some(_))
+        ) {
+            if let Err(e) = spawned.child.kill_tree() {
+                log::warn!("コマンドを終了できません: {e:?}");
+            }
+            let _ = spawned.child.wait();
+        }
+        history::append_or_warn(&record.with_exit(None, true, started.elapsed()));
+        runner::run_after_hooks(deps, &spawned.after, None, started.elapsed());
+        anyhow::bail!(
+            "コマンドがタイムアウトしました ({:?}): {}",
+            spawned.timeout.unwrap_or_default(),
+            spawned.name
+        );
+    };
+    history::append_or_warn(&record.with_exit(status.code(), false, started.elapsed()));
+    if let Some(capture) = spawned.capture.take() {
+        deliver_cli_output(capture, status.success(), &spawned.name)?;
+    }
+    runner::run_after_hooks(deps, &spawned.after, status.code(), started.elapsed());
+    if !status.success() {
+        anyhow::bail!("コマンドが失敗しました ({status}): {}", spawned.name);
+    }
+    Ok(())
+}
+
+// 取り込んだ標準出力をコピーする（正常終了時のみ）、または標準出力に出力する
+fn deliver_cli_output(
+    capture: runner::OutputCapture,
+    success: bool,
+    name: &str,
+) -> anyhow::Result<()> {
+    let output = capture
+        .wait(std::time::Duration::from_secs(1))
+        .context("標準出力を読み取れません")?;
+    if output.truncated {
+        log::warn!("出力が上限を超えたため切り詰めました: {name}");
+    }
+    match capture.mode {
+        model::commands::OutputMode::Clipboard if success => {
+            clipboard::set_text_for_cli(output.text.trim_end())?;
+        }
+        model::commands::OutputMode::Clipboard => {}
+        _ => write!(std::io::stdout(), "{}", output.text)?,
+    }
+    Ok(())
+}
+
+// `--` より前をオプション、後ろを ${query} として分ける
+fn split_query(args: &[String]) -> (&[String], String) {
+    match args.iter().position(|a| a == "--") {
+        Some(pos) => (&args[..pos], args[pos + 1..].join(" ")),
+        None => (args, String::new()),
+    }
+}
+
+fn has_yes_flag(args: &[String]) -> bool {
+    args.iter().any(|a| a == "--yes" || a == "-y")
+}
+
+fn has_flag(args: &[String], flag: &str) -> bool {
+    args.iter().any(|a| a == flag)
+}
+
+// オプション以外の最初の引数
+fn command_name(args: &[String]) -> Option<&str> {
+    args.iter()
+        .map(String::as_str)
+        .find(|a| !a.starts_with('-'))
+}
+
+// 起動せずに、どう起動されるかを標準出力に出力する
+// `cache: launch` の変数と ${clipboard} は --eval を指定した場合だけ実際に評価する
+fn print_explanation(
+    deps: &runner::LaunchDeps,
+    command: &model::commands::CommandSpec,
+    query: &str,
+    eval: bool,
+) -> anyhow::Result<()> {
+    let command = if eval {
+        runner::resolve(deps, command, query)
+    } else {
+        runner::preview(deps, command, query)
+    };
+    let mut out = std::io::stdout().lock();
+    for line in explain::explain(&command, &deps.terminal).lines() {
+        writeln!(out, "{line}")?;
+    }
+    Ok(())
+}
+
+// 展開後の引数を表示して y/N で確認する
+// 端末から実行していない場合は確認できないため、--yes を求める
+fn confirm_on_terminal(command: &model::commands::CommandSpec) -> anyhow::Result<bool> {
+    use std::io::IsTerminal;
+
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!(
+            "{} は実行前の確認が必要です。--yes を指定してください",
+            command.name()
+        );
+    }
+    let mut out = std::io::stdout().lock();
+    if command.danger() == model::commands::DangerLevel::High {
+        writeln!(out, "危険なコマンドです。内容を確認してください")?;
+    }
+    writeln!(out, "{}", runner::resolved_argv(command).join(" "))?;
+    write!(out, "{} を実行しますか？ [y/N] ", command.name())?;
+    out.flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .context("入力を読み取れません")?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
 fn app(settings: config::Settings) -> anyhow::Result<()> {
     // チャンネル準備
     let (ui_endpoint, ui_handle) = endpoint::create_ui_endpoints();
@@ -85,8 +377,46 @@ fn app(settings: config::Settings) -> anyhow::Result<()> {
     let (tray_endpoint, tray_handle) = endpoint::create_tray_endpoints();
     let (finish_tx, finish_rx) = mpsc::channel::<()>();
 
+    // 通知（Windows はタスクトレイのバルーンを使うため、トレイへの送信口を渡す）
+    let notifier = notify::system_notifier(tray_handle.tx.clone());
+
+    // lifetime: tied のプロセス（UI の終了後に終了させる）
+    let tied = std::sync::Arc::new(process_control::TiedProcesses::new());
+    let deps = runner::LaunchDeps::system(
+        std::sync::Arc::new(spawner::OsSpawner::with_tied(tied.clone())),
+        settings.terminal().clone(),
+    );
+
+    // Controller 側で使うコマンド（UI は settings から取り出す）
+    let commands = settings.clone().commands();
+
+    // 定期実行
+    let scheduler = app::scheduler::Scheduler::new(
+        &commands,
+        &history::last_scheduled_runs(),
+        Box::new(app::scheduler::SystemClock),
+    );
+
+    // 起動時・終了時のコマンド
+    let hooks = app::hooks::LifecycleHooks::new(settings.hooks(), &commands, deps.clone());
+
+    // restart のコマンドの監視
+    let supervisor =
+        app::supervisor::Supervisor::new(&commands, Box::new(app::scheduler::SystemClock));
+
     // Controller（司令塔）
-    let mut controller = Controller::new(ui_handle, hotkey_handle, tray_handle, finish_rx);
+    let mut controller = Controller::new(
+        ui_handle,
+        hotkey_handle,
+        tray_handle,
+        finish_rx,
+        notifier,
+        deps.clipboard.clone(),
+        scheduler,
+        hooks,
+        supervisor,
+        app::pipeline::LaunchPipeline::new(settings.concurrency().clone(), deps.clone()),
+    );
     std::thread::spawn(move || {
         controller.run();
     });
@@ -113,7 +443,7 @@ fn app(settings: config::Settings) -> anyhow::Result<()> {
     // std::thread::spawn(move || app::tray::start(input_tx));
 
     // UI
-    if let Err(e) = eframe_startup(settings, ui_endpoint) {
+    if let Err(e) = eframe_startup(settings, ui_endpoint, deps) {
         error!("UIでエラーが発生しました: {:?}", e);
     }
 
@@ -127,5 +457,8 @@ fn app(settings: config::Settings) -> anyhow::Result<()> {
     let _ = tray_handle.join();
     info!("タスクトレイスレッドの終了確認");
 
+    // Controller の停止後に終了させる（restart で起動し直されないように）
+    tied.terminate_all();
+
     Ok(())
 }
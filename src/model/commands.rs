@@ -1,26 +1,525 @@
+use log::warn;
 use serde::Deserialize;
-use std::collections::BTreeMap;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-pub type EnvVars = BTreeMap<String, String>;
+use crate::model::duration::ConfigDuration;
+use crate::model::restart::RestartSpec;
+use crate::model::schedule::ScheduleSpec;
+use crate::model::vars::{ExecVar, Vars, referenced_names};
+use crate::path_lookup;
 
+// 引数の定義
+// 文字列のほか、条件付き引数 `{ arg: "--proxy=${proxy}", if: proxy }` を指定できる
 #[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ArgSpec {
+    Plain(String),
+    Conditional {
+        arg: String,
+        // 指定した変数が未定義または空の場合に引数ごと省略する
+        #[serde(rename = "if", default)]
+        cond: Option<String>,
+        // 参照している変数が未定義または空の場合に引数ごと省略する
+        #[serde(default)]
+        optional: bool,
+    },
+}
+
+impl ArgSpec {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ArgSpec::Plain(arg) => arg,
+            ArgSpec::Conditional { arg, .. } => arg,
+        }
+    }
+
+    // 参照している変数名の一覧（条件の変数名を含む）
+    fn referenced_names(&self) -> Vec<&str> {
+        match self {
+            ArgSpec::Plain(arg) => referenced_names(arg),
+            ArgSpec::Conditional { arg, cond, .. } => {
+                let mut names = referenced_names(arg);
+                names.extend(cond.as_deref());
+                names
+            }
+        }
+    }
+
+    // 置換後の引数。条件を満たさない場合は空になる
+    // defer_builtin が true の場合、起動時に解決する変数を参照する条件付き引数は起動時の判定に回す
+    fn expand(&self, vars: &Vars, defer_builtin: bool) -> Vec<ArgSpec> {
+        match self {
+            ArgSpec::Plain(arg) => vars
+                .expand_arg(arg)
+                .into_iter()
+                .map(ArgSpec::Plain)
+                .collect(),
+            ArgSpec::Conditional {
+                arg,
+                cond,
+                optional,
+            } => {
+                if defer_builtin
+                    && self
+                        .referenced_names()
+                        .iter()
+                        .any(|name| vars.is_deferred(name))
+                {
+                    // 条件が起動時に解決しない変数の場合は、ここで判定する（起動時には値がない）
+                    let cond = match cond {
+                        Some(name) if !vars.is_deferred(name) => {
+                            if vars.is_unset_or_empty(name) {
+                                return vec![];
+                            }
+                            None
+                        }
+                        _ => cond.clone(),
+                    };
+                    return vec![ArgSpec::Conditional {
+                        arg: vars.expand_str(arg),
+                        cond,
+                        optional: *optional,
+                    }];
+                }
+                if let Some(name) = cond
+                    && vars.is_unset_or_empty(name)
+                {
+                    return vec![];
+                }
+                let expanded = if *optional {
+                    vars.expand_arg_if_set(arg).unwrap_or_default()
+                } else {
+                    vars.expand_arg(arg)
+                };
+                expanded.into_iter().map(ArgSpec::Plain).collect()
+            }
+        }
+    }
+}
+
+// 起動したプロセスの標準出力・標準エラーの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    // ランチャーの標準出力をそのまま引き継ぐ
+    #[default]
+    Inherit,
+    // 破棄する
+    Discard,
+    // 実行ごとのログファイルに書き出す
+    Log,
+    // 終了後に標準出力をクリップボードにコピーする
+    Clipboard,
+    // 終了後に標準出力をランチャーに表示する
+    Show,
+}
+
+impl OutputMode {
+    // 標準出力を取り込むモードか
+    pub fn captures(&self) -> bool {
+        matches!(self, OutputMode::Clipboard | OutputMode::Show)
+    }
+}
+
+// 同じコマンドを重複して起動する場合の扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstancePolicy {
+    // 制限しない
+    #[default]
+    Multiple,
+    // 実行中なら起動しない
+    Single,
+    // 実行中のものを終了してから起動する
+    Restart,
+}
+
+// 起動したプロセスとランチャーの関係
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lifetime {
+    // ランチャーが終了しても動き続ける（エディタやブラウザなど）
+    #[default]
+    Detached,
+    // 常駐アプリの終了時に終了させる（一時的な補助プロセスなど）
+    Tied,
+}
+
+// 危険度。high は起動前に確認し、確認画面で警告を表示する
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DangerLevel {
+    #[default]
+    Normal,
+    High,
+}
+
+// before / after に指定するコマンド
+// 定義済みのコマンド名、またはその場で定義するプログラムと引数 `{ program, args }`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum HookRef {
+    Name(String),
+    Inline {
+        program: String,
+        #[serde(default)]
+        args: Vec<ArgSpec>,
+    },
+}
+
+// 読み込み時に program を解決した結果
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ProgramStatus {
+    // 起動時に展開する変数を含むため、読み込み時には解決しない
+    #[default]
+    Unchecked,
+    Resolved(PathBuf),
+    // 解決できない理由
+    Unavailable(String),
+}
+
+// 置換に使った変数と値
+#[derive(Debug, Clone, PartialEq)]
+pub struct Substitution {
+    pub name: String,
+    pub value: String,
+    pub origin: VarOrigin,
+}
+
+// 変数の値の出どころ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarOrigin {
+    // env.yaml の値（`cache: load` のコマンド出力を含む）
+    Env,
+    // env.yaml の `cache: launch` のコマンド出力
+    LaunchExec,
+    Builtin,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct CommandSpec {
     name: String,
     program: String,
     #[serde(default)]
-    args: Vec<String>,
+    args: Vec<ArgSpec>,
+    #[serde(default)]
+    output: OutputMode,
+    // 標準入力に書き込むテキスト（変数を展開してから書き込み、書き込み後に閉じる）
+    #[serde(default)]
+    stdin: Option<String>,
+    // 正常終了時にも通知する（失敗時は常に通知する）
+    #[serde(default)]
+    notify: bool,
+    // 指定時間を過ぎたら終了させる（終了要求の後、応じなければ強制終了）
+    #[serde(default)]
+    timeout: Option<ConfigDuration>,
+    // 省略時は multiple（restart を指定した場合は single）
+    #[serde(default)]
+    instance: Option<InstancePolicy>,
+    // 起動前に展開後の引数を表示して確認する
+    #[serde(default)]
+    confirm: bool,
+    #[serde(default)]
+    danger: DangerLevel,
+    // 管理者権限で実行する（Windows は UAC、Linux は pkexec / sudo）
+    #[serde(default)]
+    elevate: bool,
+    // 端末を開いてその中で実行する
+    #[serde(default)]
+    terminal: bool,
+    // 端末で実行した場合、終了後も端末を閉じない
+    #[serde(default)]
+    keep_open: bool,
+    // 候補の先頭に固定する
+    #[serde(default)]
+    pin: bool,
+    // 候補の並び順のスコアに加算する（起動回数相当）
+    #[serde(default)]
+    boost: f64,
+    // 同時実行数の上限（concurrency.tags）の分類。`heavy` など
+    #[serde(default)]
+    tags: Vec<String>,
+    // 常駐アプリが定期的に実行する（cron 式または間隔）
+    #[serde(default)]
+    schedule: Option<ScheduleSpec>,
+    // 常駐アプリから起動した場合、終了したら起動し直す
+    #[serde(default)]
+    restart: Option<RestartSpec>,
+    // tied は常駐アプリの終了時に子孫プロセスごと終了させる
+    #[serde(default)]
+    lifetime: Lifetime,
+    // 起動前に順に実行し、それぞれの終了を待つ（失敗したら起動しない）
+    #[serde(default)]
+    before: Vec<HookRef>,
+    // 終了後に順に実行する。${exit_code} と ${duration_ms} を使える
+    #[serde(default)]
+    after: Vec<HookRef>,
+    // before / after を実行するコマンドにしたもの（読み込み時に作る）
+    #[serde(skip)]
+    before_hooks: Vec<CommandSpec>,
+    #[serde(skip)]
+    after_hooks: Vec<CommandSpec>,
+    // 起動時に評価するコマンド出力の変数（読み込み時に参照分を保持する）
+    #[serde(skip)]
+    launch_vars: BTreeMap<String, ExecVar>,
+    // 定義した設定ファイル（ディレクトリが相対パスの program の基準になる）
+    #[serde(skip)]
+    source: Option<PathBuf>,
+    #[serde(skip)]
+    program_status: ProgramStatus,
+    // 置換に使った変数（explain で表示する）
+    #[serde(skip)]
+    substitutions: Vec<Substitution>,
 }
 
 impl CommandSpec {
     pub fn name(&self) -> &str {
         &self.name
     }
-    pub fn program(&self) -> &str {
-        &self.program
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+    pub fn substitutions(&self) -> &[Substitution] {
+        &self.substitutions
+    }
+    pub fn program_status(&self) -> &ProgramStatus {
+        &self.program_status
+    }
+    // 一覧や候補に表示する、起動するプログラムの説明
+    pub fn program_summary(&self) -> String {
+        match &self.program_status {
+            ProgramStatus::Resolved(path) => path.display().to_string(),
+            ProgramStatus::Unavailable(reason) => format!("利用不可: {reason}"),
+            ProgramStatus::Unchecked => format!("{} (起動時に解決)", self.program),
+        }
+    }
+    pub fn is_available(&self) -> bool {
+        !matches!(self.program_status, ProgramStatus::Unavailable(_))
+    }
+    // 実際に起動するプログラム（解決済みならそのパス）
+    pub fn launch_program(&self) -> String {
+        match &self.program_status {
+            ProgramStatus::Resolved(path) => path.to_string_lossy().into_owned(),
+            _ => self.program.clone(),
+        }
+    }
+    pub fn args(&self) -> Vec<&str> {
+        self.args.iter().map(ArgSpec::as_str).collect()
+    }
+    pub fn output(&self) -> OutputMode {
+        self.output
+    }
+    pub fn stdin(&self) -> Option<&str> {
+        self.stdin.as_deref()
+    }
+    pub fn notify(&self) -> bool {
+        self.notify
+    }
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.map(|t| t.get())
     }
-    pub fn args(&self) -> &Vec<String> {
-        &self.args
+    pub fn instance(&self) -> InstancePolicy {
+        self.instance.unwrap_or(if self.restart.is_some() {
+            InstancePolicy::Single
+        } else {
+            InstancePolicy::Multiple
+        })
+    }
+    pub fn danger(&self) -> DangerLevel {
+        self.danger
+    }
+    // 起動前の確認が必要か
+    pub fn needs_confirmation(&self) -> bool {
+        self.confirm || self.danger == DangerLevel::High
+    }
+    pub fn elevate(&self) -> bool {
+        self.elevate
+    }
+    pub fn terminal(&self) -> bool {
+        self.terminal
+    }
+    pub fn keep_open(&self) -> bool {
+        self.keep_open
+    }
+    pub fn pin(&self) -> bool {
+        self.pin
+    }
+    pub fn boost(&self) -> f64 {
+        self.boost
+    }
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+    pub fn schedule(&self) -> Option<&ScheduleSpec> {
+        self.schedule.as_ref()
+    }
+    pub fn restart(&self) -> Option<&RestartSpec> {
+        self.restart.as_ref()
+    }
+    pub fn lifetime(&self) -> Lifetime {
+        self.lifetime
+    }
+    pub fn before_hooks(&self) -> &[CommandSpec] {
+        &self.before_hooks
+    }
+    pub fn after_hooks(&self) -> &[CommandSpec] {
+        &self.after_hooks
+    }
+
+    pub fn launch_vars(&self) -> &BTreeMap<String, ExecVar> {
+        &self.launch_vars
+    }
+
+    // program、args、stdin が参照している変数名の一覧
+    pub fn referenced_vars(&self) -> Vec<&str> {
+        let mut names = referenced_names(&self.program);
+        for arg in &self.args {
+            names.extend(arg.referenced_names());
+        }
+        if let Some(stdin) = &self.stdin {
+            names.extend(referenced_names(stdin));
+        }
+        names
+    }
+
+    fn expand(&self, vars: &Vars, defer_builtin: bool) -> CommandSpec {
+        let mut launch_vars = self.launch_vars.clone();
+        let mut substitutions = self.substitutions.clone();
+        for name in self.referenced_vars() {
+            if let Some(exec) = vars.exec_var(name) {
+                launch_vars.insert(name.to_string(), exec.clone());
+            }
+            if let Some(value) = vars.joined(name)
+                && !substitutions.iter().any(|s| s.name == name)
+            {
+                // 読み込み時は env.yaml、起動時は組み込み変数か起動時評価の変数
+                let origin = if defer_builtin {
+                    VarOrigin::Env
+                } else if self.launch_vars.contains_key(name) {
+                    VarOrigin::LaunchExec
+                } else {
+                    VarOrigin::Builtin
+                };
+                substitutions.push(Substitution {
+                    name: name.to_string(),
+                    value,
+                    origin,
+                });
+            }
+        }
+        CommandSpec {
+            name: self.name.clone(),
+            program: vars.expand_str(&self.program),
+            args: self
+                .args
+                .iter()
+                .flat_map(|arg| arg.expand(vars, defer_builtin))
+                .collect(),
+            output: self.output,
+            stdin: self.stdin.as_ref().map(|s| vars.expand_str(s)),
+            notify: self.notify,
+            timeout: self.timeout,
+            instance: self.instance,
+            confirm: self.confirm,
+            danger: self.danger,
+            elevate: self.elevate,
+            terminal: self.terminal,
+            keep_open: self.keep_open,
+            pin: self.pin,
+            boost: self.boost,
+            tags: self.tags.clone(),
+            schedule: self.schedule.clone(),
+            restart: self.restart.clone(),
+            lifetime: self.lifetime,
+            before: self.before.clone(),
+            after: self.after.clone(),
+            // hook は起動のたびに個別に解決するため、読み込み時だけ展開する
+            before_hooks: expand_hooks(&self.before_hooks, vars, defer_builtin),
+            after_hooks: expand_hooks(&self.after_hooks, vars, defer_builtin),
+            launch_vars,
+            source: self.source.clone(),
+            program_status: self.program_status.clone(),
+            substitutions,
+        }
+    }
+
+    // before / after の 1 件を実行するコマンドにする
+    // コマンド名の場合は、そのコマンドの before / after は実行しない（入れ子にしない）
+    // 確認が必要なコマンド（confirm / danger: high）は確認なしで実行されるため使わない
+    fn hook_command(&self, hook: &HookRef, kind: &str, all: &[CommandSpec]) -> Option<CommandSpec> {
+        match hook {
+            HookRef::Name(name) => {
+                let Some(found) = all.iter().find(|cmd| &cmd.name == name) else {
+                    warn!(
+                        "{:?}の {kind} のコマンドが見つかりません: {name}",
+                        self.name
+                    );
+                    return None;
+                };
+                if found.needs_confirmation() {
+                    warn!(
+                        "{:?}の {kind} に確認が必要なコマンドは指定できません: {name}",
+                        self.name
+                    );
+                    return None;
+                }
+                Some(CommandSpec {
+                    before: Vec::new(),
+                    after: Vec::new(),
+                    before_hooks: Vec::new(),
+                    after_hooks: Vec::new(),
+                    schedule: None,
+                    restart: None,
+                    ..found.clone()
+                })
+            }
+            HookRef::Inline { program, args } => Some(CommandSpec {
+                name: format!("{}:{kind}", self.name),
+                program: program.clone(),
+                args: args.clone(),
+                source: self.source.clone(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    // program を実行ファイルのパスに解決する。変数を含む場合は起動時に解決する
+    fn resolve_program(&mut self, cache: &mut HashMap<(String, Option<PathBuf>), ProgramStatus>) {
+        if !referenced_names(&self.program).is_empty() {
+            return;
+        }
+        let base_dir = self
+            .source
+            .as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf);
+        let key = (self.program.clone(), base_dir);
+        self.program_status = cache
+            .entry(key)
+            .or_insert_with_key(|(program, base_dir)| {
+                let base_dir = base_dir.clone().unwrap_or_default();
+                match path_lookup::resolve_program(program, &base_dir) {
+                    Ok(path) => ProgramStatus::Resolved(path),
+                    Err(reason) => ProgramStatus::Unavailable(reason),
+                }
+            })
+            .clone();
+    }
+
+    // 起動直前の置換処理（組み込み変数、起動時評価の変数の解決）
+    // 条件付き引数はここで最終的に判定する
+    pub fn expand_launch_vars(&self, vars: &Vars) -> CommandSpec {
+        self.expand(vars, false)
+    }
+}
+
+fn expand_hooks(hooks: &[CommandSpec], vars: &Vars, defer_builtin: bool) -> Vec<CommandSpec> {
+    if defer_builtin {
+        hooks.iter().map(|hook| hook.expand(vars, true)).collect()
+    } else {
+        hooks.to_vec()
     }
 }
 
@@ -39,10 +538,61 @@ impl Commands {
         self.inner.first()
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &CommandSpec> {
+        self.inner.iter()
+    }
+
     pub fn find_by_name(&self, name: &str) -> Option<&CommandSpec> {
         self.inner.iter().find(|cmd| cmd.name == name)
     }
 
+    // 定義した設定ファイルを記録する
+    pub fn with_source(mut self, path: &Path) -> Self {
+        for cmd in &mut self.inner {
+            cmd.source = Some(path.to_path_buf());
+        }
+        self
+    }
+
+    // before / after をコマンドにする（設定ファイルの結合後、変数の展開前に行う）
+    pub fn link_hooks(mut self) -> Self {
+        let linked: Vec<(Vec<CommandSpec>, Vec<CommandSpec>)> = self
+            .inner
+            .iter()
+            .map(|cmd| {
+                let link = |hooks: &[HookRef], kind: &str| -> Vec<CommandSpec> {
+                    hooks
+                        .iter()
+                        .filter_map(|hook| cmd.hook_command(hook, kind, &self.inner))
+                        .collect()
+                };
+                (link(&cmd.before, "before"), link(&cmd.after, "after"))
+            })
+            .collect();
+        for (cmd, (before, after)) in self.inner.iter_mut().zip(linked) {
+            cmd.before_hooks = before;
+            cmd.after_hooks = after;
+        }
+        self
+    }
+
+    // program を実行ファイルのパスに解決する（変数の展開後に行う）
+    // 同じ program は 1 回だけ探す。before / after のコマンドも解決する
+    pub fn resolve_programs(mut self) -> Self {
+        let mut cache: HashMap<(String, Option<PathBuf>), ProgramStatus> = HashMap::new();
+        for cmd in &mut self.inner {
+            cmd.resolve_program(&mut cache);
+            for hook in cmd
+                .before_hooks
+                .iter_mut()
+                .chain(cmd.after_hooks.iter_mut())
+            {
+                hook.resolve_program(&mut cache);
+            }
+        }
+        self
+    }
+
     // CommandsとCommandsの結合
     pub fn extend(&mut self, other: Commands) {
         self.inner.extend(other.inner);
@@ -67,46 +617,22 @@ impl Commands {
     }
 
     // 環境変数による置換処理
-    pub fn expand_vars(self, env: EnvVars) -> Self {
+    // 組み込み変数（clipboard など）と起動時評価の変数は起動時に置換するため、ここでは残す
+    pub fn expand_vars(self, vars: &Vars) -> Self {
         let new_inner: Vec<CommandSpec> = self
             .inner
-            .into_iter()
-            .map(|cmd| CommandSpec {
-                name: cmd.name.clone(),
-                program: expand_var_in_string(cmd.program, &env),
-                args: cmd
-                    .args
-                    .iter()
-                    .map(|arg| expand_var_in_string(arg.clone(), &env))
-                    .collect(),
-            })
+            .iter()
+            .map(|cmd| cmd.expand(vars, true))
             .collect();
 
         Commands { inner: new_inner }
     }
 }
 
-// 文字列中の $name を置換。未定義はそのまま残す。
-pub fn expand_var_in_string(s: String, env: &EnvVars) -> String {
-    if s.is_empty() {
-        return s;
-    }
-
-    if let Some(rest) = s.strip_prefix('$') {
-        let name: String = rest.to_string();
-        if name.is_empty() {
-            return "$".to_string();
-        }
-        if let Some(value) = env.get(&name) {
-            return value.to_string();
-        }
-    }
-    s
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model::vars::{EnvValue, EnvVars};
 
     #[test]
     fn dedup_by_name_removes_duplicates() {
@@ -115,17 +641,17 @@ mod tests {
                 CommandSpec {
                     name: "a".into(),
                     program: "p1".into(),
-                    args: vec![],
+                    ..Default::default()
                 },
                 CommandSpec {
                     name: "b".into(),
                     program: "p2".into(),
-                    args: vec![],
+                    ..Default::default()
                 },
                 CommandSpec {
                     name: "a".into(),
                     program: "p3".into(),
-                    args: vec![],
+                    ..Default::default()
                 },
             ],
         };
@@ -136,4 +662,29 @@ mod tests {
         assert_eq!(cmds.inner[1].name, "b");
         println!("cmd:{:?}", cmds);
     }
+
+    #[test]
+    fn conditional_args_are_dropped_when_var_is_unset_or_empty() {
+        let yaml = r#"
+- name: "curl"
+  program: "curl"
+  args:
+    - { arg: "--proxy=${proxy}", if: proxy }
+    - { arg: "--user=${user}", optional: true }
+    - { arg: "--debug", if: debug }
+    - "https://example.com"
+"#;
+        let specs: Vec<CommandSpec> = serde_yaml::from_str(yaml).unwrap();
+        let mut env = EnvVars::new();
+        env.insert("proxy".into(), EnvValue::Single("http://proxy:8080".into()));
+        env.insert("debug".into(), EnvValue::Single("".into()));
+        let vars = Vars::new(env, " ".into());
+
+        let cmds = Commands::new(specs).expand_vars(&vars);
+        let cmd = cmds.find_by_name("curl").unwrap();
+        assert_eq!(
+            cmd.args(),
+            vec!["--proxy=http://proxy:8080", "https://example.com"]
+        );
+    }
 }
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer};
+
+// 設定ファイル中の時間指定
+// 数値は秒として扱い、文字列は単位付き（"500ms", "30s", "5m", "1h"）で指定する
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ConfigDuration(Duration);
+
+impl ConfigDuration {
+    pub fn from_secs(secs: u64) -> Self {
+        Self(Duration::from_secs(secs))
+    }
+
+    pub fn get(&self) -> Duration {
+        self.0
+    }
+}
+
+// 単位付きの時間。表せない大きさ（桁あふれ）の場合も None
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+    let (num, unit) = s.split_at(split);
+    let num: f64 = num.parse().ok()?;
+    let secs = match unit.trim() {
+        "ms" => num / 1000.0,
+        "s" => num,
+        "m" => num * 60.0,
+        "h" => num * 3600.0,
+        "d" => num * 86400.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+impl<'de> Deserialize<'de> for ConfigDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Secs(f64),
+            Text(String),
+        }
+        match Raw::deserialize(deserializer)? {
+            // 負の値、無限大、NaN、表せない大きさの値はエラーにする（from_secs_f64 は panic する）
+            Raw::Secs(secs) => Duration::try_from_secs_f64(secs)
+                .map(Self)
+                .map_err(|_| serde::de::Error::custom(format!("時間として扱えない値です: {secs}"))),
+            Raw::Text(text) => parse_duration(&text)
+                .map(Self)
+                .ok_or_else(|| serde::de::Error::custom(format!("時間の書式が不正です: {text}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_units() {
+        assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("5m"), Some(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("10"), None);
+        assert_eq!(parse_duration("10x"), None);
+
+        assert_eq!(parse_duration("99999999999999999999999h"), None);
+
+        let d: ConfigDuration = serde_yaml::from_str("3").unwrap();
+        assert_eq!(d.get(), Duration::from_secs(3));
+        for invalid in ["-1", ".inf", ".nan", "1e300", "99999999999999999999999d"] {
+            assert!(serde_yaml::from_str::<ConfigDuration>(invalid).is_err());
+        }
+    }
+}
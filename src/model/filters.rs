@@ -0,0 +1,96 @@
+use std::path::Path;
+
+// `${name|filter}` で指定できるフィルタ
+// 未知のフィルタ名の場合は None を返す
+pub fn apply(filter: &str, value: &str) -> Option<String> {
+    let result = match filter {
+        "urlencode" => urlencode(value),
+        "upper" => value.to_uppercase(),
+        "lower" => value.to_lowercase(),
+        "trim" => value.trim().to_string(),
+        "basename" => path_part(value, |p| p.file_name()),
+        "dirname" => Path::new(value)
+            .parent()
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        "stem" => path_part(value, |p| p.file_stem()),
+        "ext" => path_part(value, |p| p.extension()),
+        "shellquote" => shellquote(value),
+        "json" => json_string(value),
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn path_part(value: &str, f: impl Fn(&Path) -> Option<&std::ffi::OsStr>) -> String {
+    f(Path::new(value))
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+// RFC 3986 の非予約文字以外をパーセントエンコードする
+fn urlencode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for b in value.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~') {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{b:02X}"));
+        }
+    }
+    out
+}
+
+// POSIX シェル向けにシングルクォートで囲む
+fn shellquote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+// JSON の文字列リテラルにする
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaping_filters() {
+        assert_eq!(
+            apply("urlencode", "a b&c/日").unwrap(),
+            "a%20b%26c%2F%E6%97%A5"
+        );
+        assert_eq!(apply("shellquote", "it's").unwrap(), r"'it'\''s'");
+        assert_eq!(apply("json", "a\"b\\c\n").unwrap(), r#""a\"b\\c\n""#);
+    }
+
+    #[test]
+    fn path_filters() {
+        assert_eq!(
+            apply("basename", "/tmp/dir/file.tar.gz").unwrap(),
+            "file.tar.gz"
+        );
+        assert_eq!(
+            apply("dirname", "/tmp/dir/file.tar.gz").unwrap(),
+            "/tmp/dir"
+        );
+        assert_eq!(apply("stem", "/tmp/dir/file.txt").unwrap(), "file");
+        assert_eq!(apply("ext", "/tmp/dir/file.txt").unwrap(), "txt");
+        assert_eq!(apply("nope", "x"), None);
+    }
+}
@@ -1 +1,7 @@
 pub mod commands;
+pub mod duration;
+pub mod filters;
+pub mod ranking;
+pub mod restart;
+pub mod schedule;
+pub mod vars;
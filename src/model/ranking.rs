@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local};
+
+use crate::model::commands::{CommandSpec, Commands};
+
+// 起動回数の重みが半分になるまでの日数
+const HALF_LIFE_DAYS: f64 = 14.0;
+
+// コマンドごとの起動日時（実行履歴から作成し、UI からの起動で追加する）
+#[derive(Debug, Clone, Default)]
+pub struct Usage {
+    launches: HashMap<String, Vec<DateTime<Local>>>,
+}
+
+impl Usage {
+    pub fn record(&mut self, name: &str, at: DateTime<Local>) {
+        self.launches.entry(name.to_string()).or_default().push(at);
+    }
+
+    // 新しい起動ほど重く数えた起動回数
+    pub fn frecency(&self, name: &str, now: DateTime<Local>) -> f64 {
+        let Some(launches) = self.launches.get(name) else {
+            return 0.0;
+        };
+        launches
+            .iter()
+            .map(|at| {
+                let age_days = (now - *at).num_seconds().max(0) as f64 / 86400.0;
+                0.5_f64.powf(age_days / HALF_LIFE_DAYS)
+            })
+            .sum()
+    }
+}
+
+// 入力に対するコマンド名の一致度（一致しない場合は None）
+// 完全一致 > 前方一致 > 部分一致 > 文字の順序だけ一致
+pub fn match_quality(query: &str, name: &str) -> Option<f64> {
+    let query = query.trim().to_lowercase();
+    let name = name.to_lowercase();
+    if query.is_empty() {
+        return Some(1.0);
+    }
+    if name == query {
+        Some(1.0)
+    } else if name.starts_with(&query) {
+        Some(0.8)
+    } else if name.contains(&query) {
+        Some(0.6)
+    } else if is_subsequence(&query, &name) {
+        Some(0.3)
+    } else {
+        None
+    }
+}
+
+fn is_subsequence(query: &str, name: &str) -> bool {
+    let mut chars = name.chars();
+    query.chars().all(|q| chars.any(|c| c == q))
+}
+
+// 候補の並び順
+// pin 指定が先頭、次にスコアの高い順、同点は定義順
+// 入力がある場合は一致するものだけに絞り込み、一致度とスコアを掛け合わせる
+pub fn rank<'a>(
+    commands: &'a Commands,
+    usage: &Usage,
+    query: &str,
+    now: DateTime<Local>,
+) -> Vec<&'a CommandSpec> {
+    let mut scored: Vec<(&CommandSpec, f64)> = commands
+        .iter()
+        .filter_map(|cmd| {
+            let quality = match_quality(query, cmd.name())?;
+            let frecency = usage.frecency(cmd.name(), now) + cmd.boost();
+            // 起動回数が多くても一致度の差を覆しすぎないよう対数で効かせる
+            Some((cmd, quality * (1.0 + frecency.max(0.0).ln_1p())))
+        })
+        .collect();
+    // sort_by は安定ソートなので同点は定義順のまま
+    scored.sort_by(|(a, a_score), (b, b_score)| {
+        b.pin().cmp(&a.pin()).then(b_score.total_cmp(a_score))
+    });
+    scored.into_iter().map(|(cmd, _)| cmd).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(yaml: &str) -> Commands {
+        Commands::new(serde_yaml::from_str(yaml).unwrap())
+    }
+
+    #[test]
+    fn ranks_by_pin_frecency_and_match_quality() {
+        let cmds = commands(
+            r#"
+- { name: "build", program: "make" }
+- { name: "deploy", program: "deploy" }
+- { name: "docs", program: "mdbook" }
+- { name: "vpn", program: "vpn", pin: true }
+- { name: "db-shell", program: "psql", boost: 2 }
+"#,
+        );
+        let now = Local::now();
+        let mut usage = Usage::default();
+        for _ in 0..3 {
+            usage.record("deploy", now - chrono::Duration::days(1));
+        }
+        // 古い起動はほとんど効かない
+        for _ in 0..3 {
+            usage.record("docs", now - chrono::Duration::days(120));
+        }
+
+        let names = |query| -> Vec<&str> {
+            rank(&cmds, &usage, query, now)
+                .iter()
+                .map(|c| c.name())
+                .collect()
+        };
+        assert_eq!(names(""), ["vpn", "deploy", "db-shell", "docs", "build"]);
+        // 前方一致は部分一致より優先される
+        assert_eq!(names("d"), ["deploy", "db-shell", "docs", "build"]);
+        assert_eq!(names("ds"), ["db-shell", "docs"]);
+        assert_eq!(names("bld"), ["build"]);
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::model::duration::ConfigDuration;
+
+// CommandSpec の `restart`
+// 常駐アプリから起動したプロセスが終了したら、間隔を倍にしながら起動し直す
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RestartSpec {
+    #[serde(default)]
+    on: RestartOn,
+    // 続けて起動し直す回数の上限
+    #[serde(default = "default_max_retries")]
+    max_retries: u32,
+    // 1 回目の待ち時間。2 回目以降は倍にする
+    #[serde(default = "default_backoff")]
+    backoff: ConfigDuration,
+}
+
+// どの終了で起動し直すか
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RestartOn {
+    // 0 以外の終了コード、またはシグナルやタイムアウトでの終了
+    #[default]
+    Failure,
+    // 正常終了を含むすべての終了
+    Always,
+}
+
+fn default_max_retries() -> u32 {
+    5
+}
+
+fn default_backoff() -> ConfigDuration {
+    ConfigDuration::from_secs(1)
+}
+
+// 待ち時間の上限（倍にし続けても、これより長くは待たない）
+pub const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+impl RestartSpec {
+    pub fn on(&self) -> RestartOn {
+        self.on
+    }
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    // 終了コード（シグナルなどで終了した場合は None）から、起動し直すかを判定する
+    pub fn should_restart(&self, exit_code: Option<i32>) -> bool {
+        match self.on {
+            RestartOn::Always => true,
+            RestartOn::Failure => exit_code != Some(0),
+        }
+    }
+
+    // attempt 回目（1 始まり）の起動し直しまでの待ち時間
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.backoff
+            .get()
+            .checked_mul(factor)
+            .map_or(MAX_BACKOFF, |delay| delay.min(MAX_BACKOFF))
+    }
+}
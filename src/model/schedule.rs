@@ -0,0 +1,306 @@
+use std::fmt;
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone, Timelike};
+use serde::Deserialize;
+
+use crate::model::duration::ConfigDuration;
+
+// CommandSpec の `schedule`
+// `{ cron: "0 9 * * 1-5" }` または `{ every: "15m" }` のどちらかを指定する
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "RawSchedule")]
+pub struct ScheduleSpec {
+    trigger: Trigger,
+    missed: MissedPolicy,
+    overlap: OverlapPolicy,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trigger {
+    Cron(CronExpr),
+    Every(ConfigDuration),
+}
+
+// ランチャーが動いていなかった、またはスリープしていたため予定時刻を過ぎた場合の扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissedPolicy {
+    // 実行せずに次の予定を待つ
+    #[default]
+    Skip,
+    // 1 回だけ実行する
+    RunOnce,
+}
+
+// 前回の実行が終わっていない場合の扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlapPolicy {
+    // 実行しない
+    #[default]
+    Skip,
+    // 重ねて実行する
+    Allow,
+}
+
+#[derive(Deserialize)]
+struct RawSchedule {
+    #[serde(default)]
+    cron: Option<String>,
+    #[serde(default)]
+    every: Option<ConfigDuration>,
+    #[serde(default)]
+    missed: MissedPolicy,
+    #[serde(default)]
+    overlap: OverlapPolicy,
+}
+
+impl TryFrom<RawSchedule> for ScheduleSpec {
+    type Error = String;
+
+    fn try_from(raw: RawSchedule) -> Result<Self, Self::Error> {
+        let trigger = match (raw.cron, raw.every) {
+            (Some(cron), None) => Trigger::Cron(CronExpr::parse(&cron)?),
+            (None, Some(every)) if every.get().is_zero() => {
+                return Err("every に 0 は指定できません".to_string());
+            }
+            (None, Some(every)) => Trigger::Every(every),
+            _ => return Err("schedule には cron と every のどちらか 1 つを指定します".to_string()),
+        };
+        Ok(Self {
+            trigger,
+            missed: raw.missed,
+            overlap: raw.overlap,
+        })
+    }
+}
+
+impl ScheduleSpec {
+    pub fn trigger(&self) -> &Trigger {
+        &self.trigger
+    }
+    pub fn missed(&self) -> MissedPolicy {
+        self.missed
+    }
+    pub fn overlap(&self) -> OverlapPolicy {
+        self.overlap
+    }
+
+    // 前回の実行（なければ基準時刻）の後の予定時刻
+    pub fn next_after(&self, last: DateTime<Local>) -> Option<DateTime<Local>> {
+        match &self.trigger {
+            Trigger::Cron(cron) => cron.next_after(last),
+            Trigger::Every(every) => Some(last + Duration::from_std(every.get()).ok()?),
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trigger::Cron(cron) => write!(f, "cron {}", cron.source),
+            Trigger::Every(every) => write!(f, "every {:?}", every.get()),
+        }
+    }
+}
+
+// cron 式（分 時 日 月 曜日 の 5 フィールド）
+// `*` `,` `-` `/` と、@hourly @daily @weekly @monthly を使える。曜日は 0（日曜）〜 7（日曜）
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronExpr {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // 日と曜日の両方を指定した場合は、どちらかに一致すればよい（cron と同じ）
+    // `*` で始まる指定（`*/2` など）は指定していないものとして扱う
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl CronExpr {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let expanded = match source.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("cron 式は 5 つのフィールドで指定します: {source}"));
+        };
+        let field = |text: &str, min: u32, max: u32| {
+            parse_field(text, min, max).map_err(|e| format!("cron 式が不正です ({e}): {source}"))
+        };
+        let mut weekdays = field(weekday, 0, 7)?;
+        // 7 も日曜日
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            source: source.trim().to_string(),
+            minutes: field(minute, 0, 59)?,
+            hours: field(hour, 0, 23)?,
+            days: field(day, 1, 31)?,
+            months: field(month, 1, 12)?,
+            weekdays,
+            days_restricted: !day.starts_with('*'),
+            weekdays_restricted: !weekday.starts_with('*'),
+        })
+    }
+
+    // 指定時刻より後で、式に一致する最初の時刻（分単位）
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let mut t = start;
+        // 条件に一致しない日・時は飛ばすため、数年分でも試行回数は少ない
+        while t < start + Duration::days(366 * 5) {
+            if !has(self.months, t.month()) {
+                t = first_of_next_month(t.date())?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !self.day_matches(t.date()) {
+                t = t.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !has(self.hours, t.hour()) {
+                t = t.with_minute(0)? + Duration::hours(1);
+                continue;
+            }
+            if !has(self.minutes, t.minute()) {
+                t += Duration::minutes(1);
+                continue;
+            }
+            // 夏時間の切り替えで存在しない時刻は飛ばす
+            match Local.from_local_datetime(&t).earliest() {
+                Some(found) => return Some(found),
+                None => t += Duration::minutes(1),
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+        if self.days_restricted && self.weekdays_restricted {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+}
+
+fn has(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+fn first_of_next_month(date: NaiveDate) -> Option<NaiveDate> {
+    match date.month() {
+        12 => NaiveDate::from_ymd_opt(date.year() + 1, 1, 1),
+        month => NaiveDate::from_ymd_opt(date.year(), month + 1, 1),
+    }
+}
+
+// 1 フィールドを、一致する値のビットを立てたマスクにする
+fn parse_field(text: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut mask = 0u64;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| format!("間隔が不正です: {part}"))?;
+                if step == 0 {
+                    return Err(format!("間隔に 0 は指定できません: {part}"));
+                }
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (parse_value(from)?, parse_value(to)?),
+                // `5/10` は 5 から最大値まで
+                None if step.is_some() => (parse_value(range)?, max),
+                None => {
+                    let value = parse_value(range)?;
+                    (value, value)
+                }
+            },
+        };
+        if from < min || to > max || from > to {
+            return Err(format!("範囲外です: {part}"));
+        }
+        for value in (from..=to).step_by(step.unwrap_or(1) as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_value(text: &str) -> Result<u32, String> {
+    text.parse()
+        .map_err(|_| format!("数値ではありません: {text}"))
+}
+
+// 予定時刻を過ぎてから何分以内なら、予定どおりの実行とみなすか
+pub const MISSED_GRACE: Duration = Duration::minutes(1);
+
+// 予定時刻を過ぎてからの経過が猶予を超えているか
+pub fn is_missed(due: DateTime<Local>, now: DateTime<Local>) -> bool {
+    now - due > MISSED_GRACE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Local> {
+        let naive = chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        Local.from_local_datetime(&naive).unwrap()
+    }
+
+    #[test]
+    fn cron_next_fire_times() {
+        let weekdays_9am = CronExpr::parse("0 9 * * 1-5").unwrap();
+        // 2026-10-16 は金曜日
+        assert_eq!(
+            weekdays_9am.next_after(at("2026-10-16 09:00")),
+            Some(at("2026-10-19 09:00"))
+        );
+        let every_15 = CronExpr::parse("*/15 * * * *").unwrap();
+        assert_eq!(
+            every_15.next_after(at("2026-10-16 23:50")),
+            Some(at("2026-10-17 00:00"))
+        );
+        // 日が `*` で始まる場合は、曜日と両方に一致する日だけ（10-17 は奇数日だが土曜日）
+        let odd_mondays = CronExpr::parse("0 0 */2 * 1").unwrap();
+        assert_eq!(
+            odd_mondays.next_after(at("2026-10-16 09:00")),
+            Some(at("2026-10-19 00:00"))
+        );
+        let monthly = CronExpr::parse("@monthly").unwrap();
+        assert_eq!(
+            monthly.next_after(at("2026-12-15 12:00")),
+            Some(at("2027-01-01 00:00"))
+        );
+
+        assert!(CronExpr::parse("0 9 * *").is_err());
+        assert!(CronExpr::parse("60 * * * *").is_err());
+        assert!(CronExpr::parse("*/0 * * * *").is_err());
+
+        let spec: ScheduleSpec = serde_yaml::from_str("{ every: 15m, missed: run_once }").unwrap();
+        assert_eq!(spec.missed(), MissedPolicy::RunOnce);
+        assert_eq!(
+            spec.next_after(at("2026-10-16 09:00")),
+            Some(at("2026-10-16 09:15"))
+        );
+        assert!(serde_yaml::from_str::<ScheduleSpec>("{ every: 15m, cron: '@daily' }").is_err());
+    }
+}
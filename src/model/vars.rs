@@ -0,0 +1,352 @@
+use log::warn;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::model::duration::ConfigDuration;
+use crate::model::filters;
+
+// env.yaml の値。文字列またはリスト
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum EnvValue {
+    Single(String),
+    List(Vec<String>),
+}
+
+impl EnvValue {
+    // インライン展開用に 1 つの文字列へ変換する
+    pub fn join(&self, separator: &str) -> String {
+        match self {
+            EnvValue::Single(s) => s.clone(),
+            EnvValue::List(items) => items.join(separator),
+        }
+    }
+
+    // 引数として展開する（リストは要素ごとに別の引数になる）
+    pub fn to_args(&self) -> Vec<String> {
+        match self {
+            EnvValue::Single(s) => vec![s.clone()],
+            EnvValue::List(items) => items.clone(),
+        }
+    }
+}
+
+impl From<String> for EnvValue {
+    fn from(value: String) -> Self {
+        EnvValue::Single(value)
+    }
+}
+
+pub type EnvVars = BTreeMap<String, EnvValue>;
+
+// コマンドの標準出力を値とする変数
+// `branch: { exec: ["git", "rev-parse", "--abbrev-ref", "HEAD"], cache: launch }`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ExecVar {
+    exec: Vec<String>,
+    #[serde(default)]
+    cache: ExecCache,
+    #[serde(default = "default_exec_timeout")]
+    timeout: ConfigDuration,
+}
+
+impl ExecVar {
+    pub fn argv(&self) -> &[String] {
+        &self.exec
+    }
+    pub fn cache(&self) -> ExecCache {
+        self.cache
+    }
+    pub fn timeout(&self) -> std::time::Duration {
+        self.timeout.get()
+    }
+}
+
+fn default_exec_timeout() -> ConfigDuration {
+    ConfigDuration::from_secs(5)
+}
+
+// コマンド出力の評価タイミング
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecCache {
+    // 設定読み込み時に一度だけ評価する
+    #[default]
+    Load,
+    // 起動のたびに評価する
+    Launch,
+}
+
+// env.yaml の 1 エントリ
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum EnvEntry {
+    Value(EnvValue),
+    Exec(ExecVar),
+}
+
+pub type EnvEntries = BTreeMap<String, EnvEntry>;
+
+pub fn default_list_separator() -> String {
+    " ".to_string()
+}
+
+// 置換用の変数一式
+#[derive(Debug, Clone)]
+pub struct Vars {
+    env: EnvVars,
+    // 起動時に評価するコマンド出力の変数
+    exec: BTreeMap<String, ExecVar>,
+    list_separator: String,
+}
+
+impl Default for Vars {
+    fn default() -> Self {
+        Self::new(EnvVars::new(), default_list_separator())
+    }
+}
+
+impl Vars {
+    pub fn new(env: EnvVars, list_separator: String) -> Self {
+        Self {
+            env,
+            exec: BTreeMap::new(),
+            list_separator,
+        }
+    }
+
+    pub fn with_exec_vars(mut self, exec: BTreeMap<String, ExecVar>) -> Self {
+        self.exec = exec;
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&EnvValue> {
+        self.env.get(name)
+    }
+
+    // 値を区切り文字で連結した文字列
+    pub fn joined(&self, name: &str) -> Option<String> {
+        self.get(name).map(|value| value.join(&self.list_separator))
+    }
+
+    pub fn exec_var(&self, name: &str) -> Option<&ExecVar> {
+        self.exec.get(name)
+    }
+
+    // 起動時まで解決を遅らせる変数かどうか
+    pub fn is_deferred(&self, name: &str) -> bool {
+        self.get(name).is_none() && (is_builtin_var(name) || self.exec_var(name).is_some())
+    }
+
+    // 変数が未定義、または空文字列（空リスト）の場合に true
+    pub fn is_unset_or_empty(&self, name: &str) -> bool {
+        match self.get(name) {
+            Some(value) => value.join("").is_empty(),
+            None => true,
+        }
+    }
+
+    // 参照している変数がすべて定義済みかつ空でない場合のみ展開する
+    // 展開結果が空文字列の場合も None を返す
+    pub fn expand_arg_if_set(&self, arg: &str) -> Option<Vec<String>> {
+        if referenced_names(arg)
+            .iter()
+            .any(|name| self.is_unset_or_empty(name))
+        {
+            return None;
+        }
+        let expanded = self.expand_arg(arg);
+        if expanded.iter().all(|a| a.is_empty()) {
+            return None;
+        }
+        Some(expanded)
+    }
+
+    // 引数 1 つを展開する
+    // `$name` 単体でリストを参照する場合は、要素を複数の引数として展開する
+    pub fn expand_arg(&self, arg: &str) -> Vec<String> {
+        if let Some(name) = whole_var_name(arg)
+            && let Some(value) = self.get(name)
+        {
+            return value.to_args();
+        }
+        vec![self.expand_str(arg)]
+    }
+
+    // 文字列を展開する
+    // - 全体が `$name` の場合はその値に置換
+    // - 文字列中の `${name}` はその値に置換（`${name|filter|...}` でフィルタを適用）
+    // リストは区切り文字で連結する。未定義はそのまま残す。
+    pub fn expand_str(&self, s: &str) -> String {
+        if let Some(name) = whole_var_name(s) {
+            return match self.get(name) {
+                Some(value) => value.join(&self.list_separator),
+                None => s.to_string(),
+            };
+        }
+        let mut out = String::with_capacity(s.len());
+        let mut rest = s;
+        while let Some(start) = rest.find("${") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let Some(end) = after.find('}') else {
+                // 閉じ括弧がない場合は展開しない
+                out.push_str(&rest[start..]);
+                return out;
+            };
+            match self.expand_token(&after[..end]) {
+                Some(value) => out.push_str(&value),
+                None => out.push_str(&rest[start..start + 2 + end + 1]),
+            }
+            rest = &after[end + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    // `${...}` の中身を展開する。展開できない場合は None
+    fn expand_token(&self, token: &str) -> Option<String> {
+        let (name, filter_names) = split_token(token);
+        let mut value = self.get(name)?.join(&self.list_separator);
+        for filter in filter_names {
+            match filters::apply(filter, &value) {
+                Some(v) => value = v,
+                None => {
+                    warn!("未知のフィルタです: {filter} (${{{token}}})");
+                    return None;
+                }
+            }
+        }
+        Some(value)
+    }
+}
+
+// `name|filter1|filter2` を変数名とフィルタ名に分割する
+fn split_token(token: &str) -> (&str, impl Iterator<Item = &str>) {
+    let mut parts = token.split('|').map(str::trim);
+    let name = parts.next().unwrap_or_default();
+    (name, parts)
+}
+
+// 全体が `$name` 形式ならば name を返す（`${...}` 形式は対象外）
+fn whole_var_name(s: &str) -> Option<&str> {
+    let name = s.strip_prefix('$')?;
+    if name.is_empty() || name.starts_with('{') {
+        return None;
+    }
+    Some(name)
+}
+
+// 起動時に解決する組み込み変数かどうか
+// `date:%Y-%m-%d` のように `:` 以降に引数を取るものがある
+pub fn is_builtin_var(name: &str) -> bool {
+    let base = name.split(':').next().unwrap_or_default();
+    matches!(
+        base,
+        "clipboard"
+            | "query"
+            | "date"
+            | "time"
+            | "uuid"
+            | "hostname"
+            | "launcher.cwd"
+            | "exit_code"
+            | "duration_ms"
+    )
+}
+
+// 文字列が参照している変数名の一覧
+pub fn referenced_names(s: &str) -> Vec<&str> {
+    if let Some(name) = whole_var_name(s) {
+        return vec![name];
+    }
+    let mut names = Vec::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            break;
+        };
+        names.push(split_token(&after[..end]).0);
+        rest = &after[end + 1..];
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> Vars {
+        let mut env = EnvVars::new();
+        env.insert("program".into(), EnvValue::Single("dev".into()));
+        env.insert(
+            "aws".into(),
+            EnvValue::List(vec![
+                "--profile".into(),
+                "x".into(),
+                "--region".into(),
+                "y".into(),
+            ]),
+        );
+        Vars::new(env, ",".into())
+    }
+
+    #[test]
+    fn env_entries_accept_exec_form() {
+        let yaml = r#"
+program: "dev"
+flags: ["-a", "-b"]
+branch: { exec: ["git", "rev-parse", "--abbrev-ref", "HEAD"], cache: launch, timeout: "2s" }
+"#;
+        let entries: EnvEntries = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            entries["program"],
+            EnvEntry::Value(EnvValue::Single("dev".into()))
+        );
+        let EnvEntry::Exec(exec) = &entries["branch"] else {
+            panic!("exec として解釈されていません");
+        };
+        assert_eq!(exec.argv()[0], "git");
+        assert_eq!(exec.cache(), ExecCache::Launch);
+        assert_eq!(exec.timeout(), std::time::Duration::from_secs(2));
+    }
+
+    #[test]
+    fn list_var_is_spliced_into_args() {
+        let vars = vars();
+        assert_eq!(
+            vars.expand_arg("$aws"),
+            vec!["--profile", "x", "--region", "y"]
+        );
+        assert_eq!(vars.expand_arg("$program"), vec!["dev"]);
+        assert_eq!(vars.expand_arg("$undefined"), vec!["$undefined"]);
+    }
+
+    #[test]
+    fn inline_list_var_is_joined() {
+        let vars = vars();
+        assert_eq!(
+            vars.expand_str("--flags=${aws}"),
+            "--flags=--profile,x,--region,y"
+        );
+        assert_eq!(
+            vars.expand_str("${program}-${undefined}"),
+            "dev-${undefined}"
+        );
+        assert_eq!(vars.expand_str("${program"), "${program");
+    }
+
+    #[test]
+    fn filters_are_applied_in_order() {
+        let mut env = EnvVars::new();
+        env.insert("query".into(), EnvValue::Single(" rust & egui ".into()));
+        let vars = Vars::new(env, " ".into());
+        assert_eq!(
+            vars.expand_str("https://example.com/?q=${query|trim|urlencode}"),
+            "https://example.com/?q=rust%20%26%20egui"
+        );
+        assert_eq!(vars.expand_str("${query | trim | upper}"), "RUST & EGUI");
+        assert_eq!(vars.expand_str("${query|unknown}"), "${query|unknown}");
+    }
+}
@@ -0,0 +1,219 @@
+use std::sync::mpsc::Sender;
+
+use crate::app::endpoint::TrayCmd;
+use crate::app::processes::{CommandOutput, ProcessInfo, ProcessState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyLevel {
+    Info,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+    pub level: NotifyLevel,
+}
+
+// デスクトップ通知の送信先
+pub trait Notifier: Send {
+    fn notify(&self, notification: Notification) -> anyhow::Result<()>;
+}
+
+// OS 標準の通知手段
+// Windows はバルーン通知（通知用のアイコンを持つタスクトレイスレッドに依頼する）
+// それ以外は freedesktop 通知
+pub fn system_notifier(tray_tx: Sender<TrayCmd>) -> Box<dyn Notifier> {
+    #[cfg(windows)]
+    {
+        Box::new(TrayBalloonNotifier { tx: tray_tx })
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = tray_tx;
+        Box::new(FreedesktopNotifier)
+    }
+}
+
+#[cfg(windows)]
+pub struct TrayBalloonNotifier {
+    tx: Sender<TrayCmd>,
+}
+
+#[cfg(windows)]
+impl Notifier for TrayBalloonNotifier {
+    fn notify(&self, notification: Notification) -> anyhow::Result<()> {
+        self.tx
+            .send(TrayCmd::Notify(notification))
+            .map_err(|_| anyhow::anyhow!("タスクトレイスレッドが終了しています"))
+    }
+}
+
+// notify-send による freedesktop 通知
+#[cfg(not(windows))]
+pub struct FreedesktopNotifier;
+
+#[cfg(not(windows))]
+impl Notifier for FreedesktopNotifier {
+    fn notify(&self, notification: Notification) -> anyhow::Result<()> {
+        use anyhow::Context;
+
+        let urgency = match notification.level {
+            NotifyLevel::Info => "normal",
+            NotifyLevel::Error => "critical",
+        };
+        std::process::Command::new("notify-send")
+            .args(["--app-name", env!("CARGO_PKG_NAME"), "--urgency", urgency])
+            .arg(&notification.title)
+            .arg(&notification.body)
+            .spawn()
+            .context("notify-send を起動できません")?;
+        Ok(())
+    }
+}
+
+// テスト用の通知先
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryNotifier {
+    pub sent: std::sync::Mutex<Vec<Notification>>,
+}
+
+#[cfg(test)]
+impl Notifier for MemoryNotifier {
+    fn notify(&self, notification: Notification) -> anyhow::Result<()> {
+        self.sent.lock().unwrap().push(notification);
+        Ok(())
+    }
+}
+
+// 起動失敗の通知
+pub fn spawn_failed(name: &str, error: &str) -> Notification {
+    Notification {
+        title: format!("{name} を起動できません"),
+        body: error.to_string(),
+        level: NotifyLevel::Error,
+    }
+}
+
+// output: clipboard のコマンドの出力をコピーした通知
+pub fn copied_output(output: &CommandOutput) -> Notification {
+    let mut body: String = output.text.trim().chars().take(80).collect();
+    if output.truncated {
+        body += "（上限を超えた分は切り詰めました）";
+    }
+    Notification {
+        title: format!("{} の出力をコピーしました", output.name),
+        body,
+        level: NotifyLevel::Info,
+    }
+}
+
+// instance: single のコマンドが実行中だった場合の通知
+pub fn already_running(name: &str, pid: u32) -> Notification {
+    Notification {
+        title: format!("{name} は既に実行中です"),
+        body: format!("pid={pid}"),
+        level: NotifyLevel::Info,
+    }
+}
+
+// restart のコマンドが上限まで起動し直しても終了した通知
+pub fn restart_gave_up(name: &str, attempts: u32) -> Notification {
+    Notification {
+        title: format!("{name} の起動し直しをやめました"),
+        body: format!("{attempts} 回起動し直しても終了しました"),
+        level: NotifyLevel::Error,
+    }
+}
+
+// プロセス終了時の通知
+// 0 以外の終了コードは常に通知し、正常終了は notify 指定のコマンドのみ通知する
+pub fn process_exited(process: &ProcessInfo) -> Option<Notification> {
+    let ProcessState::Exited(code) = process.state else {
+        return None;
+    };
+    let runtime = process
+        .runtime()
+        .map(|d| format!("{}秒", d.num_seconds()))
+        .unwrap_or_default();
+    if process.elevation_declined {
+        return Some(Notification {
+            title: format!("{} を管理者として実行できませんでした", process.name),
+            body: "管理者権限への昇格が拒否されました".to_string(),
+            level: NotifyLevel::Error,
+        });
+    }
+    if process.timed_out {
+        return Some(Notification {
+            title: format!("{} がタイムアウトしました", process.name),
+            body: format!("実行時間 {runtime} で終了させました"),
+            level: NotifyLevel::Error,
+        });
+    }
+    match code {
+        Some(0) if process.notify => Some(Notification {
+            title: format!("{} が完了しました", process.name),
+            body: format!("終了コード 0 / 実行時間 {runtime}"),
+            level: NotifyLevel::Info,
+        }),
+        Some(0) => None,
+        Some(code) => Some(Notification {
+            title: format!("{} が失敗しました", process.name),
+            body: format!("終了コード {code} / 実行時間 {runtime}"),
+            level: NotifyLevel::Error,
+        }),
+        None => Some(Notification {
+            title: format!("{} が異常終了しました", process.name),
+            body: format!("シグナルにより終了 / 実行時間 {runtime}"),
+            level: NotifyLevel::Error,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::LaunchSource;
+    use chrono::Local;
+
+    fn exited(code: Option<i32>, notify: bool) -> ProcessInfo {
+        let started_at = Local::now() - chrono::Duration::seconds(90);
+        ProcessInfo {
+            pid: 1,
+            name: "build".into(),
+            argv: vec!["make".into()],
+            source: LaunchSource::Ui,
+            started_at,
+            ended_at: Some(Local::now()),
+            state: ProcessState::Exited(code),
+            notify,
+            timed_out: false,
+            elevation_declined: false,
+            tags: vec![],
+        }
+    }
+
+    #[test]
+    fn exit_notifications() {
+        let notifier = MemoryNotifier::default();
+        let cases = [
+            exited(Some(0), false),
+            exited(Some(0), true),
+            exited(Some(2), false),
+        ];
+        for process in &cases {
+            if let Some(n) = process_exited(process) {
+                notifier.notify(n).unwrap();
+            }
+        }
+
+        let sent = notifier.sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].level, NotifyLevel::Info);
+        assert_eq!(sent[0].body, "終了コード 0 / 実行時間 90秒");
+        assert_eq!(sent[1].level, NotifyLevel::Error);
+        assert_eq!(sent[1].title, "build が失敗しました");
+    }
+}
@@ -0,0 +1,165 @@
+use std::path::{Path, PathBuf};
+
+// プログラム名から実行ファイルを探す
+// パス区切りを含む場合はそのパスを、含まない場合は PATH 上を探す
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return candidates(path).into_iter().find(|p| is_executable(p));
+    }
+    let dirs = std::env::var_os("PATH")?;
+    std::env::split_paths(&dirs)
+        .flat_map(|dir| candidates(&dir.join(program)))
+        .find(|p| is_executable(p))
+}
+
+// 設定ファイルの program を実行ファイルのパスに解決する
+// 相対パスは設定ファイルのディレクトリを基準にする。解決できない場合は理由を返す
+pub fn resolve_program(program: &str, base_dir: &Path) -> Result<PathBuf, String> {
+    if program.is_empty() {
+        return Err("program が空です".to_string());
+    }
+    let path = Path::new(program);
+    if path.components().count() == 1 {
+        return find_program(program).ok_or_else(|| format!("PATH 上に見つかりません: {program}"));
+    }
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        base_dir.join(path)
+    };
+    let candidates = candidates(&path);
+    if let Some(found) = candidates.iter().find(|p| is_executable(p)) {
+        return Ok(found.clone());
+    }
+    match candidates.iter().find(|p| p.is_file()) {
+        Some(file) => Err(format!("実行権限がありません: {}", file.display())),
+        // Windows で拡張子が PATHEXT にないファイル
+        None if path.is_file() => Err(format!(
+            "実行できる拡張子 (PATHEXT) ではありません: {}",
+            path.display()
+        )),
+        None => Err(format!("ファイルがありません: {}", path.display())),
+    }
+}
+
+// 実行ファイルの候補
+// Windows は拡張子を省略できるため PATHEXT の拡張子を補った候補を返す
+fn candidates(path: &Path) -> Vec<PathBuf> {
+    if cfg!(windows) {
+        windows_candidates(path, &pathext())
+    } else {
+        vec![path.to_path_buf()]
+    }
+}
+
+// 拡張子が PATHEXT にあればそのまま、なければ PATHEXT の拡張子を順に補ったもの
+// 拡張子のない名前そのもの（npm などが置くシェルスクリプト）は実行できないため候補にしない
+fn windows_candidates(path: &Path, exts: &[String]) -> Vec<PathBuf> {
+    if has_executable_extension(path, exts) {
+        return vec![path.to_path_buf()];
+    }
+    exts.iter()
+        .map(|ext| {
+            let mut name = path.as_os_str().to_owned();
+            name.push(ext);
+            PathBuf::from(name)
+        })
+        .collect()
+}
+
+fn pathext() -> Vec<String> {
+    std::env::var("PATHEXT")
+        .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".into())
+        .split(';')
+        .filter(|ext| !ext.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn has_executable_extension(path: &Path, exts: &[String]) -> bool {
+    path.extension().is_some_and(|ext| {
+        exts.iter().any(|e| {
+            e.trim_start_matches('.')
+                .eq_ignore_ascii_case(&ext.to_string_lossy())
+        })
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+// Windows は実行権限のビットがないため、拡張子が PATHEXT にあるファイルを実行できるものとする
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file() && has_executable_extension(path, &pathext())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn resolves_relative_path_against_config_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("path_lookup_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("scripts")).unwrap();
+        let script = dir.join("scripts").join("backup.sh");
+        std::fs::write(&script, "#!/bin/sh\n").unwrap();
+
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(
+            resolve_program("scripts/backup.sh", &dir)
+                .unwrap_err()
+                .starts_with("実行権限がありません")
+        );
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(resolve_program("scripts/backup.sh", &dir), Ok(script));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unresolvable_program_has_reason() {
+        let dir = std::env::temp_dir();
+        assert!(
+            resolve_program("scripts/no-such-script", &dir)
+                .unwrap_err()
+                .starts_with("ファイルがありません")
+        );
+        assert!(
+            resolve_program("no-such-tool-xyz", &dir)
+                .unwrap_err()
+                .starts_with("PATH 上に見つかりません")
+        );
+        assert!(resolve_program("", &dir).is_err());
+    }
+
+    #[test]
+    fn windows_tries_pathext_before_bare_name() {
+        let exts: Vec<String> = [".COM", ".EXE", ".CMD"].map(String::from).into();
+        // 拡張子のない code（シェルスクリプト）より code.cmd を優先する
+        assert_eq!(
+            windows_candidates(Path::new("bin/code"), &exts),
+            [
+                PathBuf::from("bin/code.COM"),
+                PathBuf::from("bin/code.EXE"),
+                PathBuf::from("bin/code.CMD"),
+            ]
+        );
+        assert_eq!(
+            windows_candidates(Path::new("code.cmd"), &exts),
+            [PathBuf::from("code.cmd")]
+        );
+        // PATHEXT にない拡張子は名前の一部として補う
+        assert_eq!(
+            windows_candidates(Path::new("tool.v2"), &exts)[1],
+            PathBuf::from("tool.v2.EXE")
+        );
+    }
+}
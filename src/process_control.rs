@@ -0,0 +1,363 @@
+use std::process::Command;
+
+use anyhow::{Context, bail};
+use log::info;
+#[cfg(windows)]
+use log::warn;
+
+// プロセスへの終了要求（強制終了ではない）
+// Windows はウィンドウへの WM_CLOSE、Unix は SIGTERM
+pub fn terminate(pid: u32) -> anyhow::Result<()> {
+    #[cfg(windows)]
+    {
+        run_quiet(Command::new("taskkill").args(["/PID", &pid.to_string()]))
+    }
+    #[cfg(unix)]
+    {
+        run_quiet(Command::new("kill").args(["-TERM", &pid.to_string()]))
+    }
+}
+
+// プロセスと、その子孫プロセスを強制終了する
+pub fn kill_tree(pid: u32) -> anyhow::Result<()> {
+    #[cfg(windows)]
+    {
+        run_quiet(Command::new("taskkill").args(["/T", "/F", "/PID", &pid.to_string()]))
+    }
+    #[cfg(unix)]
+    {
+        // 子から先に終了させ、親による再起動を避ける
+        let mut pids = descendants(pid);
+        pids.reverse();
+        pids.push(pid);
+        let pids: Vec<String> = pids.iter().map(|p| p.to_string()).collect();
+        run_quiet(Command::new("kill").arg("-KILL").args(&pids))
+    }
+}
+
+// lifetime: tied のプロセス。ランチャーの終了時にまとめて終了させる
+// Linux はプロセスごとに新しいプロセスグループを作り（起動時に spawner が指定する）、その ID を記録する
+// Windows はランチャーが持つジョブオブジェクトに割り当てる（ランチャーが異常終了した場合も終了する）
+pub struct TiedProcesses {
+    #[cfg(unix)]
+    groups: std::sync::Mutex<Vec<u32>>,
+    // 作成できなかった場合は 0
+    #[cfg(windows)]
+    job: windows_sys::Win32::Foundation::HANDLE,
+}
+
+// 終了要求から強制終了に切り替えるまでの猶予（Linux のみ）
+#[cfg(unix)]
+const TIED_GRACE: std::time::Duration = std::time::Duration::from_secs(2);
+// 猶予の間にグループの終了を確認する間隔
+#[cfg(unix)]
+const TIED_POLL: std::time::Duration = std::time::Duration::from_millis(100);
+
+impl TiedProcesses {
+    pub fn new() -> Self {
+        #[cfg(unix)]
+        {
+            Self {
+                groups: std::sync::Mutex::new(Vec::new()),
+            }
+        }
+        #[cfg(windows)]
+        {
+            let job = unsafe { create_kill_on_close_job() };
+            if job == 0 {
+                warn!("ジョブオブジェクトを作成できません。lifetime: tied は無視されます");
+            }
+            Self { job }
+        }
+    }
+
+    // ジョブオブジェクトを作成できたか。作成できなかった場合は割り当てない
+    #[cfg(windows)]
+    pub fn is_active(&self) -> bool {
+        self.job != 0
+    }
+
+    // 起動したプロセスを登録する
+    // Linux は process_group(0) で起動したプロセス（pid がプロセスグループ ID になる）
+    // Windows は CREATE_SUSPENDED で起動し、割り当ててから再開する（子孫プロセスがジョブの外で起動しないように）
+    pub fn adopt(&self, pid: u32) -> anyhow::Result<()> {
+        #[cfg(unix)]
+        {
+            let mut groups = self
+                .groups
+                .lock()
+                .map_err(|_| anyhow::anyhow!("プロセスグループの一覧を更新できません"))?;
+            // 終了したグループは、その ID が別のプロセスに再利用される前に外す
+            let alive = live_groups();
+            groups.retain(|pgid| alive.contains(pgid));
+            groups.push(pid);
+            Ok(())
+        }
+        #[cfg(windows)]
+        unsafe {
+            use windows_sys::Win32::Foundation::CloseHandle;
+            use windows_sys::Win32::System::JobObjects::AssignProcessToJobObject;
+            use windows_sys::Win32::System::Threading::{
+                OpenProcess, PROCESS_SET_QUOTA, PROCESS_TERMINATE,
+            };
+
+            if self.job == 0 {
+                bail!("ジョブオブジェクトがありません");
+            }
+            let process = OpenProcess(PROCESS_SET_QUOTA | PROCESS_TERMINATE, 0, pid);
+            if process == 0 {
+                bail!("プロセスを開けません: pid={pid}");
+            }
+            let assigned = AssignProcessToJobObject(self.job, process);
+            CloseHandle(process);
+            if assigned == 0 {
+                bail!("プロセスをジョブオブジェクトに割り当てられません: pid={pid}");
+            }
+            Ok(())
+        }
+    }
+
+    // 登録したプロセス（と、その子孫）をすべて終了させる
+    // Linux は各プロセスグループに SIGTERM を送り、猶予の後に残っていれば SIGKILL を送る
+    // 終了済みのグループには送らない（ID が別のプロセスに再利用されている場合があるため）
+    pub fn terminate_all(&self) {
+        #[cfg(unix)]
+        {
+            let groups = match self.groups.lock() {
+                Ok(mut groups) => std::mem::take(&mut *groups),
+                Err(_) => return,
+            };
+            let alive = |groups: &[u32]| -> Vec<u32> {
+                let live = live_groups();
+                groups
+                    .iter()
+                    .copied()
+                    .filter(|pgid| live.contains(pgid))
+                    .collect()
+            };
+            let mut groups = alive(&groups);
+            if groups.is_empty() {
+                return;
+            }
+            info!("lifetime: tied のプロセスを終了させます: pgid={groups:?}");
+            for pgid in &groups {
+                let _ = run_quiet(Command::new("kill").args(["-TERM", "--", &format!("-{pgid}")]));
+            }
+            let deadline = std::time::Instant::now() + TIED_GRACE;
+            while !groups.is_empty() && std::time::Instant::now() < deadline {
+                std::thread::sleep(TIED_POLL);
+                groups = alive(&groups);
+            }
+            for pgid in &groups {
+                let _ = run_quiet(Command::new("kill").args(["-KILL", "--", &format!("-{pgid}")]));
+            }
+        }
+        #[cfg(windows)]
+        unsafe {
+            use windows_sys::Win32::System::JobObjects::TerminateJobObject;
+
+            if self.job == 0 {
+                return;
+            }
+            info!("lifetime: tied のプロセスを終了させます");
+            if TerminateJobObject(self.job, 1) == 0 {
+                warn!("ジョブオブジェクトのプロセスを終了できません");
+            }
+        }
+    }
+}
+
+impl Default for TiedProcesses {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ハンドルを閉じたとき（ランチャーの終了時）に、割り当てたプロセスを終了させるジョブオブジェクト
+#[cfg(windows)]
+unsafe fn create_kill_on_close_job() -> windows_sys::Win32::Foundation::HANDLE {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::JobObjects::{
+        CreateJobObjectW, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JobObjectExtendedLimitInformation, SetInformationJobObject,
+    };
+
+    unsafe {
+        let job = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+        if job == 0 {
+            return 0;
+        }
+        let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+        info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        let ok = SetInformationJobObject(
+            job,
+            JobObjectExtendedLimitInformation,
+            &info as *const _ as *const std::ffi::c_void,
+            std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+        );
+        if ok == 0 {
+            CloseHandle(job);
+            return 0;
+        }
+        job
+    }
+}
+
+// CREATE_SUSPENDED で起動したプロセスの最初のスレッドを再開する
+#[cfg(windows)]
+pub fn resume(pid: u32) -> anyhow::Result<()> {
+    use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, TH32CS_SNAPTHREAD, THREADENTRY32, Thread32First, Thread32Next,
+    };
+    use windows_sys::Win32::System::Threading::{OpenThread, ResumeThread, THREAD_SUSPEND_RESUME};
+
+    // SAFETY: スナップショットとスレッドのハンドルは、このスコープで閉じる
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0);
+        if snapshot == INVALID_HANDLE_VALUE {
+            bail!("スレッドの一覧を取得できません: pid={pid}");
+        }
+        let mut entry: THREADENTRY32 = std::mem::zeroed();
+        entry.dwSize = std::mem::size_of::<THREADENTRY32>() as u32;
+        let mut found = Thread32First(snapshot, &mut entry) != 0;
+        let mut resumed = false;
+        while found {
+            if entry.th32OwnerProcessID == pid {
+                let thread = OpenThread(THREAD_SUSPEND_RESUME, 0, entry.th32ThreadID);
+                if thread != 0 {
+                    resumed = ResumeThread(thread) != u32::MAX;
+                    CloseHandle(thread);
+                }
+                break;
+            }
+            found = Thread32Next(snapshot, &mut entry) != 0;
+        }
+        CloseHandle(snapshot);
+        if !resumed {
+            bail!("停止した状態で起動したプロセスを再開できません: pid={pid}");
+        }
+        Ok(())
+    }
+}
+
+fn run_quiet(cmd: &mut Command) -> anyhow::Result<()> {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        // コンソールウィンドウを表示しない
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = cmd
+        .output()
+        .with_context(|| format!("プロセス終了コマンドを実行できません: {cmd:?}"))?;
+    if !output.status.success() {
+        bail!(
+            "プロセスを終了できません ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+// /proc のプロセスの状態
+#[cfg(unix)]
+struct ProcStat {
+    pid: u32,
+    state: char,
+    ppid: u32,
+    pgrp: u32,
+}
+
+#[cfg(unix)]
+fn proc_stats() -> Vec<ProcStat> {
+    let mut stats = Vec::new();
+    if let Ok(entries) = std::fs::read_dir("/proc") {
+        for entry in entries.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+                continue;
+            };
+            let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else {
+                continue;
+            };
+            // "pid (comm) state ppid pgrp ..." comm に空白や括弧を含むことがあるので最後の ')' 以降を読む
+            let Some((_, rest)) = stat.rsplit_once(')') else {
+                continue;
+            };
+            let mut fields = rest.split_whitespace();
+            let (Some(state), Some(ppid), Some(pgrp)) = (
+                fields.next().and_then(|s| s.chars().next()),
+                fields.next().and_then(|s| s.parse().ok()),
+                fields.next().and_then(|s| s.parse().ok()),
+            ) else {
+                continue;
+            };
+            stats.push(ProcStat {
+                pid,
+                state,
+                ppid,
+                pgrp,
+            });
+        }
+    }
+    stats
+}
+
+// 終了していない（ゾンビでない）プロセスが属するプロセスグループ
+// メンバーが残っている間、グループ ID は別のプロセスに再利用されない
+#[cfg(unix)]
+fn live_groups() -> std::collections::HashSet<u32> {
+    proc_stats()
+        .into_iter()
+        .filter(|p| p.state != 'Z')
+        .map(|p| p.pgrp)
+        .collect()
+}
+
+// /proc から子孫プロセスの pid を列挙する（親が先）
+#[cfg(unix)]
+fn descendants(root: u32) -> Vec<u32> {
+    let stats = proc_stats();
+    let mut result = Vec::new();
+    let mut queue = vec![root];
+    while let Some(parent) = queue.pop() {
+        for p in stats.iter().filter(|p| p.ppid == parent) {
+            result.push(p.pid);
+            queue.push(p.pid);
+        }
+    }
+    result
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::spawner::{OsSpawner, SpawnRequest, Spawner};
+
+    #[test]
+    fn terminate_all_stops_tied_process_groups() {
+        let tied = Arc::new(TiedProcesses::new());
+        let spawner = OsSpawner::with_tied(tied.clone());
+        let argv = |script: &str| vec!["sh".into(), "-c".into(), script.into()];
+
+        // 子プロセスを待つシェル（グループごと終了させないと sleep が残る）
+        let mut request = SpawnRequest::new(argv("sleep 30 & wait"));
+        request.tied = true;
+        let mut tied_child = spawner.spawn(request).unwrap();
+        let mut detached = spawner.spawn(SpawnRequest::new(argv("sleep 30"))).unwrap();
+
+        let started = std::time::Instant::now();
+        tied.terminate_all();
+        let status = tied_child.wait().unwrap();
+        assert!(!status.success());
+        // SIGTERM で終了したため、猶予を待たずに戻る
+        assert!(started.elapsed() < TIED_GRACE);
+        assert!(detached.try_wait().unwrap().is_none());
+        detached.kill().unwrap();
+        let _ = detached.wait();
+    }
+}
@@ -0,0 +1,197 @@
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use log::warn;
+
+use crate::config;
+
+// ログディレクトリ全体の上限サイズ。超えた分は古いファイルから削除する
+const MAX_TOTAL_BYTES: u64 = 20 * 1024 * 1024;
+
+// output: log の出力先
+// 起動処理に注入し、テストでは一時ディレクトリに作成するものに差し替える
+pub trait LogSink: Send + Sync {
+    fn create(&self, command_name: &str) -> anyhow::Result<(PathBuf, File)>;
+}
+
+// 設定ディレクトリの logs に作成する
+pub struct RunLogDir;
+
+impl LogSink for RunLogDir {
+    fn create(&self, command_name: &str) -> anyhow::Result<(PathBuf, File)> {
+        create(command_name)
+    }
+}
+
+// テスト用。指定したディレクトリに作成する
+#[cfg(test)]
+pub struct TempLogDir(pub PathBuf);
+
+#[cfg(test)]
+impl LogSink for TempLogDir {
+    fn create(&self, command_name: &str) -> anyhow::Result<(PathBuf, File)> {
+        create_in(&self.0, command_name, MAX_TOTAL_BYTES)
+    }
+}
+
+// 実行ごとのログファイルを作成する
+// ファイル名は `<日時>_<コマンド名>.log`（コマンド名は encode_name で変換する）
+// 同じ時刻の同じコマンドのログがある場合は `<日時>-<連番>_<コマンド名>.log` にする（上書きしない）
+pub fn create(command_name: &str) -> anyhow::Result<(PathBuf, File)> {
+    let dir = config::log_dir()?;
+    create_in(&dir, command_name, MAX_TOTAL_BYTES)
+}
+
+fn create_in(
+    dir: &Path,
+    command_name: &str,
+    max_total_bytes: u64,
+) -> anyhow::Result<(PathBuf, File)> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("ログディレクトリを作成できません: {}", dir.display()))?;
+
+    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S-%3f").to_string();
+    let name = encode_name(command_name);
+    let mut attempt = 0u32;
+    loop {
+        let path = match attempt {
+            0 => dir.join(format!("{timestamp}_{name}.log")),
+            n => dir.join(format!("{timestamp}-{n}_{name}.log")),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => {
+                prune(dir, &path, max_total_bytes);
+                return Ok((path, file));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("ログファイルを作成できません: {}", path.display()));
+            }
+        }
+    }
+}
+
+// 最新のログファイル。コマンド名を指定した場合はそのコマンドのログに絞り込む
+pub fn latest(command_name: Option<&str>) -> anyhow::Result<Option<PathBuf>> {
+    let dir = config::log_dir()?;
+    Ok(latest_in(&dir, command_name))
+}
+
+fn latest_in(dir: &Path, command_name: Option<&str>) -> Option<PathBuf> {
+    let encoded = command_name.map(encode_name);
+    log_files(dir)
+        .into_iter()
+        .map(|(path, _)| path)
+        .filter(|path| match &encoded {
+            Some(encoded) => logged_name(path) == Some(encoded.as_str()),
+            None => true,
+        })
+        .max_by(|a, b| file_name(a).cmp(file_name(b)))
+}
+
+// ファイル名のコマンド名の部分（日時の後の最初の `_` から拡張子まで）
+// 日時は `_` を含まず、コマンド名の `_` は変換されているため、区切りは一意に決まる
+fn logged_name(path: &Path) -> Option<&str> {
+    file_name(path)
+        .strip_suffix(".log")?
+        .split_once('_')
+        .map(|(_, name)| name)
+}
+
+// 合計サイズが上限を超えた分を古いものから削除する（keep は削除しない）
+fn prune(dir: &Path, keep: &Path, max_total_bytes: u64) {
+    let mut files = log_files(dir);
+    // ファイル名の先頭が日時なので、名前の降順が新しい順になる
+    files.sort_by(|(a, _), (b, _)| file_name(b).cmp(file_name(a)));
+
+    let mut total = 0;
+    for (path, size) in files {
+        total += size;
+        if total > max_total_bytes
+            && path != keep
+            && let Err(e) = fs::remove_file(&path)
+        {
+            warn!("古いログを削除できません: {}: {e:?}", path.display());
+        }
+    }
+}
+
+fn log_files(dir: &Path) -> Vec<(PathBuf, u64)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "log"))
+        .filter_map(|entry| {
+            let size = entry.metadata().ok()?.len();
+            Some((entry.path(), size))
+        })
+        .collect()
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+}
+
+// コマンド名をファイル名に使える形にする
+// 英数字と `-` 以外は `%XX`（UTF-8 のバイト）にし、異なるコマンド名が同じ名前にならないようにする
+fn encode_name(name: &str) -> String {
+    let mut encoded = String::new();
+    for c in name.chars() {
+        if c.is_alphanumeric() || c == '-' {
+            encoded.push(c);
+        } else {
+            let mut buf = [0u8; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                encoded.push_str(&format!("%{b:02X}"));
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn old_logs_are_pruned_by_total_size() {
+        let dir = std::env::temp_dir().join(format!("run_log_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["20250101-000000-000_a.log", "20250102-000000-000_b.log"] {
+            let mut f = File::create(dir.join(name)).unwrap();
+            f.write_all(&[b'x'; 60]).unwrap();
+        }
+
+        let (path, _) = create_in(&dir, "my cmd", 100).unwrap();
+        assert!(file_name(&path).ends_with("_my%20cmd.log"));
+        assert!(!dir.join("20250101-000000-000_a.log").exists());
+        assert!(dir.join("20250102-000000-000_b.log").exists());
+
+        assert_eq!(
+            latest_in(&dir, Some("b")),
+            Some(dir.join("20250102-000000-000_b.log"))
+        );
+        assert_eq!(latest_in(&dir, None), Some(path.clone()));
+
+        // 名前の一部が一致するだけのコマンドや、変換前に同じ名前になるコマンドのログは返さない
+        let (other, _) = create_in(&dir, "my_cmd", 1000).unwrap();
+        assert_eq!(latest_in(&dir, Some("cmd")), None);
+        assert_eq!(latest_in(&dir, Some("my cmd")), Some(path));
+        assert_eq!(latest_in(&dir, Some("my_cmd")), Some(other));
+
+        // 同じ時刻に同じコマンドを起動しても、前のログを上書きしない
+        let (first, _) = create_in(&dir, "burst", 1000).unwrap();
+        let (second, _) = create_in(&dir, "burst", 1000).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(logged_name(&second), Some("burst"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
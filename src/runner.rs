@@ -1,13 +1,828 @@
-use std::process::{Child, Command};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::{IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::sync::{Arc, mpsc};
+use std::time::{Duration, Instant};
 
-use anyhow::Context;
+use anyhow::{Context, bail};
+use log::{info, warn};
+use serde::Deserialize;
 
-use crate::model::commands::CommandSpec;
+use crate::app::processes::ProcessInfo;
+use crate::clipboard::{ClipboardProvider, SystemClipboard};
+use crate::context::LaunchContext;
+use crate::elevation::{self, ElevationStrategy};
+use crate::history::LaunchSource;
+use crate::model::commands::{CommandSpec, InstancePolicy, Lifetime, OutputMode, ProgramStatus};
+use crate::run_log::{LogSink, RunLogDir};
+use crate::spawner::{ChildProcess, Redirect, SpawnRequest, Spawner};
+use crate::terminal::TerminalConfig;
 
-pub fn spawn_command(command: &CommandSpec) -> anyhow::Result<Child> {
-    let mut cmd = Command::new(command.program());
-    cmd.args(command.args());
+// 起動に使うもの（プロセスの起動、昇格、ログファイル、端末、クリップボード）
+// 使う側（UI、Controller、CLI）に注入し、テストでは記録用の実装に差し替える
+#[derive(Clone)]
+pub struct LaunchDeps {
+    pub spawner: Arc<dyn Spawner>,
+    pub elevation: Arc<dyn ElevationStrategy>,
+    pub logs: Arc<dyn LogSink>,
+    pub terminal: TerminalConfig,
+    // ${clipboard} の評価と output: clipboard のコピーに使う
+    pub clipboard: Arc<dyn ClipboardProvider>,
+}
+
+impl LaunchDeps {
+    // OS の昇格方法とクリップボード、設定ディレクトリのログを使う
+    pub fn system(spawner: Arc<dyn Spawner>, terminal: TerminalConfig) -> Self {
+        Self {
+            spawner,
+            elevation: Arc::from(elevation::system_strategy()),
+            logs: Arc::new(RunLogDir),
+            terminal,
+            clipboard: Arc::new(SystemClipboard::default()),
+        }
+    }
+
+    // テスト用。昇格は MockElevation、ログは一時ディレクトリ、クリップボードはメモリ上
+    #[cfg(test)]
+    pub fn recording(spawner: crate::spawner::RecordingSpawner) -> Self {
+        Self {
+            spawner: Arc::new(spawner),
+            elevation: Arc::new(crate::elevation::MockElevation::default()),
+            logs: Arc::new(crate::run_log::TempLogDir(
+                std::env::temp_dir().join(format!("launch_deps_{}", std::process::id())),
+            )),
+            terminal: TerminalConfig::default(),
+            clipboard: Arc::new(crate::clipboard::MemoryClipboard::default()),
+        }
+    }
+}
+
+// 起動したプロセスと、起動時の指定
+pub struct Spawned {
+    pub name: String,
+    pub child: Box<dyn ChildProcess>,
+    // 実際に渡したプログラムと引数（変数展開後）
+    pub argv: Vec<String>,
+    pub notify: bool,
+    pub timeout: Option<Duration>,
+    // 管理者権限で実行した場合、昇格を拒否されたことを示す終了コード
+    pub declined_exit_code: Option<i32>,
+    // output: clipboard / show の場合の標準出力の取り込み
+    pub capture: Option<OutputCapture>,
+    // 終了後に実行するコマンド（after）
+    pub after: Vec<CommandSpec>,
+    pub tags: Vec<String>,
+    // output: log の場合の出力先のログファイル
+    pub log: Option<PathBuf>,
+}
+
+// 取り込む標準出力の上限。超えた分は読み捨てる
+pub const MAX_CAPTURE_BYTES: usize = 1024 * 1024;
+// 出力を取り込むコマンドに timeout の指定がない場合の上限
+pub const DEFAULT_CAPTURE_TIMEOUT: Duration = Duration::from_secs(30);
+// before / after のコマンドに timeout の指定がない場合の待ち時間の上限
+pub const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedOutput {
+    pub text: String,
+    // 上限を超えて切り詰めたか
+    pub truncated: bool,
+}
+
+// 標準出力をバックグラウンドで読み取り、終了後に受け取る
+pub struct OutputCapture {
+    pub mode: OutputMode,
+    rx: mpsc::Receiver<CapturedOutput>,
+}
+
+impl OutputCapture {
+    fn start(mode: OutputMode, stdout: impl Read + Send + 'static) -> Self {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = tx.send(read_capped(stdout, MAX_CAPTURE_BYTES));
+        });
+        Self { mode, rx }
+    }
+
+    // 読み取りの完了を待つ
+    // プロセスの終了後も孫プロセスが標準出力を保持している場合があるため、待ち時間を区切る
+    pub fn wait(&self, timeout: Duration) -> Option<CapturedOutput> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+}
+
+// 標準入力に書き込んで閉じる
+// 子プロセスが読み取る前に出力で詰まる場合があるため、別スレッドで書き込む
+fn write_in_background(mut pipe: impl Write + Send + 'static, text: String, name: String) {
+    std::thread::spawn(move || {
+        if let Err(e) = pipe.write_all(text.as_bytes()) {
+            warn!("{name:?}の標準入力に書き込めません: {e:?}");
+        }
+        // drop で閉じる
+    });
+}
+
+fn read_capped(mut reader: impl Read, max_bytes: usize) -> CapturedOutput {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let mut truncated = false;
+    loop {
+        match reader.read(&mut chunk) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                let room = max_bytes.saturating_sub(buf.len());
+                if n > room {
+                    truncated = true;
+                }
+                // 上限を超えた分もパイプが詰まらないよう読み続ける
+                buf.extend_from_slice(&chunk[..n.min(room)]);
+            }
+        }
+    }
+    CapturedOutput {
+        text: String::from_utf8_lossy(&buf).into_owned(),
+        truncated,
+    }
+}
+
+// 組み込み変数と起動時評価の変数を解決する（起動直前に呼ぶ）
+// query はランチャーでコマンド名に続けて入力したテキスト
+pub fn resolve(deps: &LaunchDeps, command: &CommandSpec, query: &str) -> CommandSpec {
+    LaunchContext::new(deps.clipboard.as_ref(), deps.spawner.as_ref())
+        .with_query(query)
+        .resolve(command)
+}
+
+// 起動せずに表示する用（explain、UI のプレビュー）
+// コマンド出力の変数と ${clipboard} は評価せず、プレースホルダーにする
+pub fn preview(deps: &LaunchDeps, command: &CommandSpec, query: &str) -> CommandSpec {
+    LaunchContext::new(deps.clipboard.as_ref(), deps.spawner.as_ref())
+        .with_query(query)
+        .placeholders()
+        .resolve(command)
+}
+
+// resolve 済みのコマンドを起動する
+// before のコマンドを順に実行し、すべて成功した場合だけ起動する
+pub fn spawn_command(deps: &LaunchDeps, command: &CommandSpec) -> anyhow::Result<Spawned> {
+    // 設定の読み込み後にインストールされた場合もあるため、起動は試みる
+    if let ProgramStatus::Unavailable(reason) = command.program_status() {
+        warn!(
+            "{:?}の実行ファイルが見つかりませんでしたが、起動を試みます: {reason}",
+            command.name()
+        );
+    }
+    for hook in command.before_hooks() {
+        let hook = resolve(deps, hook, "");
+        run_hook(deps, &hook).with_context(|| {
+            format!(
+                "{} の before のコマンドが失敗したため起動しません",
+                command.name()
+            )
+        })?;
+    }
+    let elevation = deps.elevation.as_ref();
+    let mut request = SpawnRequest::new(launch_argv(command, &deps.terminal, elevation)?);
+    request.tied = command.lifetime() == Lifetime::Tied;
+
+    let captures = command.output().captures() && !command.terminal();
+    let mut log = None;
+    match command.output() {
+        OutputMode::Inherit => {}
+        // 端末で実行する場合、出力は端末に表示される
+        _ if command.terminal() => {
+            warn!(
+                "{:?}は端末で実行するため output の指定を無視します",
+                command.name()
+            );
+        }
+        OutputMode::Discard => {
+            request.stdout = Redirect::Null;
+            request.stderr = Redirect::Null;
+        }
+        OutputMode::Log => {
+            let (path, file) = deps.logs.create(command.name())?;
+            let stderr = file
+                .try_clone()
+                .context("ログファイルのハンドルを複製できません")?;
+            request.stdout = Redirect::File(file);
+            request.stderr = Redirect::File(stderr);
+            info!(
+                "{:?}の出力をログに記録します: {}",
+                command.name(),
+                path.display()
+            );
+            log = Some(path);
+        }
+        OutputMode::Clipboard | OutputMode::Show => {
+            request.stdin = Redirect::Null;
+            request.stdout = Redirect::Piped;
+            request.stderr = Redirect::Null;
+        }
+    }
+
+    let stdin = match command.stdin() {
+        Some(_) if command.terminal() => {
+            warn!(
+                "{:?}は端末で実行するため stdin の指定を無視します",
+                command.name()
+            );
+            None
+        }
+        Some(text) => {
+            request.stdin = Redirect::Piped;
+            Some(text.to_string())
+        }
+        None => None,
+    };
+
+    let argv = request.argv.clone();
+    let spawned = if command.elevate() {
+        elevation.spawn(deps.spawner.as_ref(), request)
+    } else {
+        deps.spawner.spawn(request)
+    };
+    let mut child =
+        spawned.with_context(|| format!("コマンドを起動できません: {}", command.name()))?;
+    if let Some(text) = stdin
+        && let Some(pipe) = child.take_stdin()
+    {
+        write_in_background(pipe, text, command.name().to_string());
+    }
+    let capture = if captures {
+        child
+            .take_stdout()
+            .map(|stdout| OutputCapture::start(command.output(), stdout))
+    } else {
+        None
+    };
+    // 出力を取り込むコマンドは終了しないと結果を返せないため、必ず時間を区切る
+    let timeout = match command.timeout() {
+        None if captures => Some(DEFAULT_CAPTURE_TIMEOUT),
+        timeout => timeout,
+    };
+    Ok(Spawned {
+        name: command.name().to_string(),
+        child,
+        argv,
+        notify: command.notify(),
+        timeout,
+        declined_exit_code: if command.elevate() {
+            elevation.declined_exit_code()
+        } else {
+            None
+        },
+        capture,
+        after: command.after_hooks().to_vec(),
+        tags: command.tags().to_vec(),
+        log,
+    })
+}
+
+// after のコマンドを順に実行し、それぞれの終了を待つ
+// 失敗しても残りのコマンドは実行する
+pub fn run_after_hooks(
+    deps: &LaunchDeps,
+    hooks: &[CommandSpec],
+    exit_code: Option<i32>,
+    duration: Duration,
+) {
+    for hook in hooks {
+        let hook = LaunchContext::new(deps.clipboard.as_ref(), deps.spawner.as_ref())
+            .with_exit(exit_code, duration)
+            .resolve(hook);
+        if let Err(e) = run_hook(deps, &hook) {
+            warn!("after のコマンドが失敗しました: {e:?}");
+        }
+    }
+}
+
+// before / after のコマンドを 1 つ実行して終了を待つ
+// 時間内に終わらない場合は強制終了し、0 以外の終了コードとともにエラーにする
+fn run_hook(deps: &LaunchDeps, hook: &CommandSpec) -> anyhow::Result<()> {
+    info!("{:?}を実行します", hook.name());
+    let mut spawned = spawn_command(deps, hook)?;
+    let timeout = hook.timeout().unwrap_or(DEFAULT_HOOK_TIMEOUT);
+    let Some(status) = wait_timeout(spawned.child.as_mut(), timeout)? else {
+        if let Err(e) = spawned.child.kill_tree() {
+            warn!("コマンドを終了できません: {e:?}");
+        }
+        let _ = spawned.child.wait();
+        bail!(
+            "コマンドがタイムアウトしました ({timeout:?}): {}",
+            hook.name()
+        );
+    };
+    if !status.success() {
+        bail!("コマンドが失敗しました ({status}): {}", hook.name());
+    }
+    Ok(())
+}
+
+// 起動せずに、実際に起動する argv を組み立てる（explain 用）
+pub fn planned_argv(
+    command: &CommandSpec,
+    terminal: &TerminalConfig,
+) -> anyhow::Result<Vec<String>> {
+    launch_argv(command, terminal, elevation::system_strategy().as_ref())
+}
+
+// 実際に起動する argv
+// 端末内でパスワードを入力できるよう、昇格したコマンドを端末で包む
+fn launch_argv(
+    command: &CommandSpec,
+    terminal: &TerminalConfig,
+    elevation: &dyn ElevationStrategy,
+) -> anyhow::Result<Vec<String>> {
+    let mut argv = resolved_argv(command);
+    if command.elevate() {
+        // sudo はパスワードを端末で入力するため、端末がなければ起動しても応答を待ち続ける
+        if elevation.needs_terminal() && !command.terminal() && !std::io::stdin().is_terminal() {
+            bail!(
+                "{} を管理者として実行するには pkexec をインストールするか、terminal: true を指定してください（sudo は端末がないとパスワードを入力できません）",
+                command.name()
+            );
+        }
+        argv = elevation.wrap(argv)?;
+    }
+    if command.terminal() {
+        argv = terminal.wrap(argv, command.keep_open())?;
+    }
+    Ok(argv)
+}
+
+// setting.yaml の `concurrency`
+// 省略時は全体の上限なし、`heavy` タグは 1 つずつ
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawConcurrency")]
+pub struct ConcurrencyConfig {
+    // 全体で同時に実行するコマンド数の上限（省略時は制限なし）
+    max_running: Option<usize>,
+    // タグごとの上限
+    tags: BTreeMap<String, usize>,
+}
+
+#[derive(Deserialize)]
+struct RawConcurrency {
+    #[serde(default)]
+    max_running: Option<usize>,
+    #[serde(default = "default_tag_limits")]
+    tags: BTreeMap<String, usize>,
+}
+
+// 上限が 0 のコマンドはいつまでも起動できないため、読み込み時にエラーにする
+impl TryFrom<RawConcurrency> for ConcurrencyConfig {
+    type Error = String;
+
+    fn try_from(raw: RawConcurrency) -> Result<Self, Self::Error> {
+        if raw.max_running == Some(0) {
+            return Err("max_running に 0 は指定できません".to_string());
+        }
+        if let Some((tag, _)) = raw.tags.iter().find(|(_, max)| **max == 0) {
+            return Err(format!("tags の {tag} に 0 は指定できません"));
+        }
+        Ok(Self {
+            max_running: raw.max_running,
+            tags: raw.tags,
+        })
+    }
+}
+
+impl Default for ConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            max_running: None,
+            tags: default_tag_limits(),
+        }
+    }
+}
+
+fn default_tag_limits() -> BTreeMap<String, usize> {
+    BTreeMap::from([("heavy".to_string(), 1)])
+}
+
+// 上限の種類（全体、またはタグ）
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Limit {
+    Global,
+    Tag(String),
+}
+
+impl ConcurrencyConfig {
+    // コマンドにかかる上限と、その値
+    fn limits_of<'a>(&'a self, tags: &'a [String]) -> impl Iterator<Item = (Limit, usize)> + 'a {
+        let global = self.max_running.map(|max| (Limit::Global, max));
+        let tagged = tags.iter().filter_map(|tag| {
+            self.tags
+                .get(tag)
+                .map(|max| (Limit::Tag(tag.clone()), *max))
+        });
+        global.into_iter().chain(tagged)
+    }
+}
+
+// 待機中の起動（id は取り消しに使う）
+#[derive(Debug, Clone)]
+pub struct Queued {
+    pub id: u64,
+    pub command: CommandSpec,
+    pub source: LaunchSource,
+    // false の場合は起動するスレッドで resolve する（query は空）
+    pub resolved: bool,
+}
+
+// 同時実行数の上限に達している間、起動を待たせる
+// LaunchPipeline が保持し、Controller のループから pop_ready で空きを確認する
+pub struct LaunchQueue {
+    limits: ConcurrencyConfig,
+    // 待機中の起動。先頭から順に起動する
+    waiting: VecDeque<Queued>,
+}
+
+impl LaunchQueue {
+    pub fn new(limits: ConcurrencyConfig) -> Self {
+        Self {
+            limits,
+            waiting: VecDeque::new(),
+        }
+    }
+
+    // 待機中の起動。先頭が 1 番目
+    pub fn waiting(&self) -> impl Iterator<Item = &Queued> {
+        self.waiting.iter()
+    }
+
+    // 起動を依頼する。すぐに起動できるかは pop_ready で判定する
+    pub fn push(&mut self, queued: Queued) {
+        self.waiting.push_back(queued);
+    }
+
+    // 待機中の起動を取り消す
+    pub fn cancel(&mut self, id: u64) -> Option<Queued> {
+        let index = self.waiting.iter().position(|q| q.id == id)?;
+        self.waiting.remove(index)
+    }
+
+    // 上限に空きがある待機中のコマンドを、先頭から順に取り出す
+    // 先に待っているコマンドを追い越さないよう、空きを待っている上限にかかるものは後ろも待たせる
+    // 一覧の実行中のものと、起動処理中（before の実行中など）のもの（starting はそのタグ）を数える
+    pub fn pop_ready(
+        &mut self,
+        processes: &[ProcessInfo],
+        starting: &[Vec<String>],
+    ) -> Vec<Queued> {
+        let mut running: HashMap<Limit, usize> = HashMap::new();
+        let running_tags = processes
+            .iter()
+            .filter(|p| p.is_running())
+            .map(|p| p.tags.as_slice())
+            .chain(starting.iter().map(Vec::as_slice));
+        for tags in running_tags {
+            // 上限の設定がないタグも全体の数には数える
+            *running.entry(Limit::Global).or_default() += 1;
+            for tag in tags {
+                *running.entry(Limit::Tag(tag.clone())).or_default() += 1;
+            }
+        }
+
+        let mut blocked: HashSet<Limit> = HashSet::new();
+        let mut ready = Vec::new();
+        let mut still_waiting = VecDeque::new();
+        for queued in self.waiting.drain(..) {
+            // 空きがない、または先に待っているコマンドがある上限
+            let full: Vec<Limit> = self
+                .limits
+                .limits_of(queued.command.tags())
+                .filter(|(limit, max)| {
+                    blocked.contains(limit) || running.get(limit).copied().unwrap_or(0) >= *max
+                })
+                .map(|(limit, _)| limit)
+                .collect();
+            if full.is_empty() {
+                *running.entry(Limit::Global).or_default() += 1;
+                for tag in queued.command.tags() {
+                    *running.entry(Limit::Tag(tag.clone())).or_default() += 1;
+                }
+                ready.push(queued);
+            } else {
+                blocked.extend(full);
+                still_waiting.push_back(queued);
+            }
+        }
+        self.waiting = still_waiting;
+        ready
+    }
+}
+
+// 同じコマンドの重複起動の判定結果
+#[derive(Debug, PartialEq, Eq)]
+pub enum InstanceCheck {
+    Launch,
+    // single: 実行中のため起動しない
+    AlreadyRunning(u32),
+    // restart: 実行中のものを終了してから起動する
+    Restart(Vec<u32>),
+}
+
+// このランチャーから起動して実行中のプロセスをもとに、起動してよいか判定する
+pub fn check_instance(command: &CommandSpec, processes: &[ProcessInfo]) -> InstanceCheck {
+    let running: Vec<u32> = processes
+        .iter()
+        .filter(|p| p.is_running() && p.name == command.name())
+        .map(|p| p.pid)
+        .collect();
+    match (command.instance(), running.first()) {
+        (InstancePolicy::Multiple, _) | (_, None) => InstanceCheck::Launch,
+        (InstancePolicy::Single, Some(pid)) => InstanceCheck::AlreadyRunning(*pid),
+        (InstancePolicy::Restart, Some(_)) => InstanceCheck::Restart(running),
+    }
+}
+
+// 実行するプログラムと引数を 1 つの配列にする
+// program は読み込み時に解決したパスにする
+pub fn resolved_argv(command: &CommandSpec) -> Vec<String> {
+    std::iter::once(command.launch_program())
+        .chain(command.args().into_iter().map(str::to_string))
+        .collect()
+}
+
+// コマンドを実行し、トリムした標準出力を返す
+// タイムアウトした場合はプロセスを終了させてエラーにする
+pub fn capture_stdout(
+    spawner: &dyn Spawner,
+    argv: &[String],
+    timeout: Duration,
+) -> anyhow::Result<String> {
+    let program = argv.first().context("実行するコマンドが空です")?;
+    let mut request = SpawnRequest::new(argv.to_vec());
+    request.stdin = Redirect::Null;
+    request.stdout = Redirect::Piped;
+    request.stderr = Redirect::Piped;
+    let mut child = spawner
+        .spawn(request)
+        .with_context(|| format!("コマンドを起動できません: {program}"))?;
+
+    // パイプが詰まらないよう、終了待ちと並行して読み取る
+    let stdout = read_to_end_in_background(child.take_stdout());
+    let stderr = read_to_end_in_background(child.take_stderr());
+
+    let Some(status) = wait_timeout(child.as_mut(), timeout)? else {
+        if let Err(e) = child.kill_tree() {
+            warn!("コマンドを終了できません: {e:?}");
+        }
+        let _ = child.wait();
+        bail!("コマンドがタイムアウトしました ({timeout:?}): {program}");
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if !status.success() {
+        bail!(
+            "コマンドが失敗しました ({status}): {program}: {}",
+            String::from_utf8_lossy(&stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&stdout).trim().to_string())
+}
+
+// 指定時間までプロセスの終了を待つ。タイムアウトした場合は None
+pub fn wait_timeout(
+    child: &mut dyn ChildProcess,
+    timeout: Duration,
+) -> anyhow::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait().context("プロセスの状態を取得できません")?
+        {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn read_to_end_in_background<R: Read + Send + 'static>(
+    reader: Option<R>,
+) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut reader) = reader {
+            let _ = reader.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::processes::ProcessState;
+    use crate::elevation::MockElevation;
+    use crate::model::commands::Commands;
+    use crate::model::vars::Vars;
+    use crate::spawner::RecordingSpawner;
+
+    fn process(name: &str, pid: u32, state: ProcessState) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: name.into(),
+            argv: vec![],
+            source: LaunchSource::Ui,
+            started_at: chrono::Local::now(),
+            ended_at: None,
+            state,
+            notify: false,
+            timed_out: false,
+            elevation_declined: false,
+            tags: vec![],
+        }
+    }
+
+    #[test]
+    fn captured_output_is_capped() {
+        let out = read_capped(&b"0123456789"[..], 4);
+        assert_eq!(
+            out,
+            CapturedOutput {
+                text: "0123".into(),
+                truncated: true
+            }
+        );
+        assert!(!read_capped(&b"abc"[..], 4).truncated);
+    }
+
+    #[test]
+    fn elevated_commands_are_wrapped_by_strategy() {
+        let specs: Vec<CommandSpec> = serde_yaml::from_str(
+            r#"
+- { name: "set-ip", program: "netsh", args: ["interface", "ip", "set", "address", "name=Ethernet", "dhcp"], elevate: true }
+- { name: "ping", program: "ping", args: ["localhost"] }
+"#,
+        )
+        .unwrap();
+        let mock = MockElevation::default();
+        let terminal = TerminalConfig::default();
+
+        assert_eq!(
+            launch_argv(&specs[0], &terminal, &mock).unwrap(),
+            [
+                "elevate",
+                "netsh",
+                "interface",
+                "ip",
+                "set",
+                "address",
+                "name=Ethernet",
+                "dhcp"
+            ]
+        );
+        assert_eq!(
+            launch_argv(&specs[1], &terminal, &mock).unwrap(),
+            ["ping", "localhost"]
+        );
+        assert_eq!(
+            *mock.calls.lock().unwrap(),
+            [vec![
+                "netsh",
+                "interface",
+                "ip",
+                "set",
+                "address",
+                "name=Ethernet",
+                "dhcp"
+            ]]
+        );
+    }
+
+    #[test]
+    fn before_hooks_gate_launch_and_after_hooks_get_exit_vars() {
+        let commands = Commands::new(
+            serde_yaml::from_str(
+                r#"
+- { name: "mount", program: "net", args: ["use", "Z:"] }
+- { name: "format", program: "format", args: ["Z:"], danger: high }
+- name: "open-share"
+  program: "explorer"
+  args: ["Z:"]
+  before: ["mount", "format"]
+  after: [{ program: "notify", args: ["exit=${exit_code}", "${duration_ms}ms"] }]
+"#,
+            )
+            .unwrap(),
+        )
+        .link_hooks()
+        .expand_vars(&Vars::default());
+        let command = commands.find_by_name("open-share").unwrap();
+        // 確認が必要なコマンドは before / after に使わない
+        assert_eq!(command.before_hooks().len(), 1);
+
+        let spawner = RecordingSpawner::exiting(0);
+        let deps = LaunchDeps::recording(spawner.clone());
+        let spawned = spawn_command(&deps, command).unwrap();
+        run_after_hooks(&deps, &spawned.after, Some(3), Duration::from_millis(1500));
+        assert_eq!(
+            spawner.argvs(),
+            [
+                vec!["net", "use", "Z:"],
+                vec!["explorer", "Z:"],
+                vec!["notify", "exit=3", "1500ms"]
+            ]
+        );
+
+        // before が失敗した場合は起動しない
+        let failing = RecordingSpawner::exiting(1);
+        assert!(spawn_command(&LaunchDeps::recording(failing.clone()), command).is_err());
+        assert_eq!(failing.argvs(), [vec!["net", "use", "Z:"]]);
+    }
+
+    #[test]
+    fn launch_queue_limits_heavy_commands_and_keeps_order() {
+        let specs: Vec<CommandSpec> = serde_yaml::from_str(
+            r#"
+- { name: "build", program: "cargo", tags: ["heavy"] }
+- { name: "vm", program: "vagrant", tags: ["heavy"] }
+- { name: "notes", program: "notepad" }
+"#,
+        )
+        .unwrap();
+        let limits: ConcurrencyConfig = serde_yaml::from_str("max_running: 2").unwrap();
+        let mut queue = LaunchQueue::new(limits);
+        let names = |queued: Vec<Queued>| -> Vec<String> {
+            queued
+                .iter()
+                .map(|q| q.command.name().to_string())
+                .collect()
+        };
+        let queued = |id: u64, command: &CommandSpec| Queued {
+            id,
+            command: command.clone(),
+            source: LaunchSource::Ui,
+            resolved: true,
+        };
+
+        queue.push(queued(1, &specs[0]));
+        assert_eq!(names(queue.pop_ready(&[], &[])), ["build"]);
+        let mut build = process("build", 1, ProcessState::Running);
+        build.tags = vec!["heavy".into()];
+        queue.push(queued(2, &specs[1]));
+        queue.push(queued(3, &specs[0]));
+        queue.push(queued(4, &specs[2]));
+        assert_eq!(names(queue.pop_ready(&[build.clone()], &[])), ["notes"]);
+        let notes = process("notes", 2, ProcessState::Running);
+        let waiting: Vec<&str> = queue.waiting().map(|q| q.command.name()).collect();
+        assert_eq!(waiting, ["vm", "build"]);
+
+        // build が終わると、先に待っていた vm だけを起動する
+        build.state = ProcessState::Exited(Some(0));
+        // 起動処理中のものも数える
+        let starting = [vec!["heavy".to_string()]];
+        assert!(
+            queue
+                .pop_ready(&[build.clone(), notes.clone()], &starting)
+                .is_empty()
+        );
+        assert_eq!(names(queue.pop_ready(&[build, notes], &[])), ["vm"]);
+        assert_eq!(
+            queue.cancel(3).map(|q| q.command.name().to_string()),
+            Some("build".into())
+        );
+        assert!(queue.waiting().next().is_none());
+
+        // 上限 0 は読み込み時にエラーにする
+        assert!(serde_yaml::from_str::<ConcurrencyConfig>("max_running: 0").is_err());
+        assert!(serde_yaml::from_str::<ConcurrencyConfig>("tags: { heavy: 0 }").is_err());
+    }
+
+    #[test]
+    fn instance_policy_checks_running_processes_of_same_command() {
+        let specs: Vec<CommandSpec> = serde_yaml::from_str(
+            r#"
+- { name: "vpn", program: "vpn", instance: single }
+- { name: "tail", program: "tail", instance: restart }
+- { name: "shell", program: "sh" }
+"#,
+        )
+        .unwrap();
+        let processes = [
+            process("vpn", 10, ProcessState::Running),
+            process("tail", 20, ProcessState::Exited(Some(0))),
+            process("tail", 21, ProcessState::Running),
+            process("shell", 30, ProcessState::Running),
+        ];
 
-    cmd.spawn()
-        .with_context(|| format!("コマンドを起動できません: {}", command.name()))
+        assert_eq!(
+            check_instance(&specs[0], &processes),
+            InstanceCheck::AlreadyRunning(10)
+        );
+        assert_eq!(
+            check_instance(&specs[1], &processes),
+            InstanceCheck::Restart(vec![21])
+        );
+        assert_eq!(check_instance(&specs[2], &processes), InstanceCheck::Launch);
+        assert_eq!(check_instance(&specs[0], &[]), InstanceCheck::Launch);
+    }
 }
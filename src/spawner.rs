@@ -0,0 +1,256 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::Arc;
+
+use anyhow::Context;
+use log::warn;
+
+use crate::process_control::{self, TiedProcesses};
+
+// 標準入出力の接続先
+pub enum Redirect {
+    Inherit,
+    Null,
+    Piped,
+    File(File),
+}
+
+impl Redirect {
+    fn into_stdio(self) -> Stdio {
+        match self {
+            Redirect::Inherit => Stdio::inherit(),
+            Redirect::Null => Stdio::null(),
+            Redirect::Piped => Stdio::piped(),
+            Redirect::File(file) => Stdio::from(file),
+        }
+    }
+}
+
+// 起動の依頼内容（プログラムと引数、標準入出力の接続先）
+pub struct SpawnRequest {
+    pub argv: Vec<String>,
+    pub stdin: Redirect,
+    pub stdout: Redirect,
+    pub stderr: Redirect,
+    // lifetime: tied。ランチャーの終了時に終了させる
+    pub tied: bool,
+}
+
+impl SpawnRequest {
+    pub fn new(argv: Vec<String>) -> Self {
+        Self {
+            argv,
+            stdin: Redirect::Inherit,
+            stdout: Redirect::Inherit,
+            stderr: Redirect::Inherit,
+            tied: false,
+        }
+    }
+}
+
+// 起動したプロセスの操作
+pub trait ChildProcess: Send {
+    fn id(&self) -> u32;
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>>;
+    fn wait(&mut self) -> io::Result<ExitStatus>;
+    fn kill(&mut self) -> io::Result<()>;
+    // 子孫プロセスを含めて強制終了する
+    fn kill_tree(&mut self) -> anyhow::Result<()>;
+    // Piped を指定した標準入力・標準出力を取り出す
+    fn take_stdin(&mut self) -> Option<Box<dyn Write + Send>>;
+    fn take_stdout(&mut self) -> Option<Box<dyn Read + Send>>;
+    fn take_stderr(&mut self) -> Option<Box<dyn Read + Send>>;
+}
+
+impl ChildProcess for Child {
+    fn id(&self) -> u32 {
+        Child::id(self)
+    }
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        Child::try_wait(self)
+    }
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        Child::wait(self)
+    }
+    fn kill(&mut self) -> io::Result<()> {
+        Child::kill(self)
+    }
+    fn kill_tree(&mut self) -> anyhow::Result<()> {
+        kill_tree_or_self(self)
+    }
+    fn take_stdin(&mut self) -> Option<Box<dyn Write + Send>> {
+        self.stdin
+            .take()
+            .map(|pipe| Box::new(pipe) as Box<dyn Write + Send>)
+    }
+    fn take_stdout(&mut self) -> Option<Box<dyn Read + Send>> {
+        self.stdout
+            .take()
+            .map(|pipe| Box::new(pipe) as Box<dyn Read + Send>)
+    }
+    fn take_stderr(&mut self) -> Option<Box<dyn Read + Send>> {
+        self.stderr
+            .take()
+            .map(|pipe| Box::new(pipe) as Box<dyn Read + Send>)
+    }
+}
+
+// 子孫プロセスを含めて強制終了する
+// ツリーの終了に失敗した場合でも、少なくとも起動したプロセス自体は終了させる
+pub fn kill_tree_or_self(child: &mut dyn ChildProcess) -> anyhow::Result<()> {
+    if let Err(e) = process_control::kill_tree(child.id()) {
+        warn!("プロセスツリーを終了できません: pid={}: {e:?}", child.id());
+        child.kill().context("プロセスを終了できません")?;
+    }
+    Ok(())
+}
+
+// プロセスの起動
+// 起動処理を使う側（UI、CLI など）に注入し、テストでは記録用の実装に差し替える
+pub trait Spawner: Send + Sync {
+    fn spawn(&self, request: SpawnRequest) -> anyhow::Result<Box<dyn ChildProcess>>;
+}
+
+// OS のプロセスとして起動する
+// tied を持たない場合（CLI）は、lifetime: tied のプロセスも切り離して起動する
+#[derive(Default, Clone)]
+pub struct OsSpawner {
+    tied: Option<Arc<TiedProcesses>>,
+}
+
+impl OsSpawner {
+    pub fn with_tied(tied: Arc<TiedProcesses>) -> Self {
+        Self { tied: Some(tied) }
+    }
+}
+
+impl Spawner for OsSpawner {
+    fn spawn(&self, request: SpawnRequest) -> anyhow::Result<Box<dyn ChildProcess>> {
+        let (program, args) = request
+            .argv
+            .split_first()
+            .context("実行するコマンドが空です")?;
+        let tied = self.tied.as_ref().filter(|_| request.tied);
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .stdin(request.stdin.into_stdio())
+            .stdout(request.stdout.into_stdio())
+            .stderr(request.stderr.into_stdio());
+        // 子孫プロセスごと終了させられるよう、新しいプロセスグループで起動する
+        #[cfg(unix)]
+        if tied.is_some() {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+        // 子孫プロセスもジョブに入るよう、ジョブに割り当てるまで停止した状態で起動する
+        #[cfg(windows)]
+        let suspended = tied.is_some_and(|tied| tied.is_active());
+        #[cfg(windows)]
+        if suspended {
+            use std::os::windows::process::CommandExt;
+            command.creation_flags(windows_sys::Win32::System::Threading::CREATE_SUSPENDED);
+        }
+        #[cfg_attr(unix, allow(unused_mut))]
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("プロセスを起動できません: {program}"))?;
+        if let Some(tied) = tied
+            && let Err(e) = tied.adopt(child.id())
+        {
+            warn!("ランチャーの終了時に終了させるプロセスとして登録できません: {e:?}");
+        }
+        #[cfg(windows)]
+        if suspended && let Err(e) = process_control::resume(child.id()) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+        Ok(Box::new(child))
+    }
+}
+
+// テスト用。起動せずに argv を記録する
+// clone したものは記録を共有するため、注入した後も記録を確認できる
+#[cfg(test)]
+#[derive(Default, Clone)]
+pub struct RecordingSpawner {
+    argvs: std::sync::Arc<std::sync::Mutex<Vec<Vec<String>>>>,
+    // 指定した場合、起動したプロセスはすぐにこの終了コードで終了する
+    exit_code: Option<i32>,
+}
+
+#[cfg(test)]
+impl RecordingSpawner {
+    pub fn exiting(exit_code: i32) -> Self {
+        Self {
+            exit_code: Some(exit_code),
+            ..Self::default()
+        }
+    }
+    pub fn argvs(&self) -> Vec<Vec<String>> {
+        self.argvs.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl Spawner for RecordingSpawner {
+    fn spawn(&self, request: SpawnRequest) -> anyhow::Result<Box<dyn ChildProcess>> {
+        let mut argvs = self.argvs.lock().unwrap();
+        argvs.push(request.argv);
+        Ok(Box::new(FakeChild {
+            pid: 10000 + argvs.len() as u32,
+            exit_code: self.exit_code,
+        }))
+    }
+}
+
+// exit_code を指定しなければ、終了しないプロセスとして振る舞う
+#[cfg(test)]
+struct FakeChild {
+    pid: u32,
+    exit_code: Option<i32>,
+}
+
+#[cfg(test)]
+fn exit_status(code: i32) -> ExitStatus {
+    #[cfg(unix)]
+    use std::os::unix::process::ExitStatusExt;
+    #[cfg(windows)]
+    use std::os::windows::process::ExitStatusExt;
+    #[cfg(unix)]
+    return ExitStatus::from_raw(code << 8);
+    #[cfg(windows)]
+    return ExitStatus::from_raw(code as u32);
+}
+
+#[cfg(test)]
+impl ChildProcess for FakeChild {
+    fn id(&self) -> u32 {
+        self.pid
+    }
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        Ok(self.exit_code.map(exit_status))
+    }
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        self.exit_code
+            .map(exit_status)
+            .ok_or_else(|| io::Error::other("FakeChild は終了しません"))
+    }
+    fn kill(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+    fn kill_tree(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+    fn take_stdin(&mut self) -> Option<Box<dyn Write + Send>> {
+        None
+    }
+    fn take_stdout(&mut self) -> Option<Box<dyn Read + Send>> {
+        None
+    }
+    fn take_stderr(&mut self) -> Option<Box<dyn Read + Send>> {
+        None
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::path_lookup;
+
+// コマンドを開く端末
+// 起動するコマンドのプログラムと引数は args の後ろに追加する
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TerminalSpec {
+    program: String,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+impl TerminalSpec {
+    fn new(program: &str, args: &[&str]) -> Self {
+        Self {
+            program: program.to_string(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+// setting.yaml の `terminal`
+#[derive(Debug, Clone, Deserialize)]
+pub struct TerminalConfig {
+    // 上から順に探し、最初に見つかった端末を使う
+    #[serde(default = "default_candidates")]
+    candidates: Vec<TerminalSpec>,
+}
+
+impl Default for TerminalConfig {
+    fn default() -> Self {
+        Self {
+            candidates: default_candidates(),
+        }
+    }
+}
+
+#[cfg(windows)]
+fn default_candidates() -> Vec<TerminalSpec> {
+    vec![
+        TerminalSpec::new("wt.exe", &["new-tab", "--"]),
+        TerminalSpec::new("conhost.exe", &[]),
+    ]
+}
+
+#[cfg(not(windows))]
+fn default_candidates() -> Vec<TerminalSpec> {
+    vec![
+        TerminalSpec::new("x-terminal-emulator", &["-e"]),
+        TerminalSpec::new("gnome-terminal", &["--"]),
+        TerminalSpec::new("konsole", &["-e"]),
+        TerminalSpec::new("alacritty", &["-e"]),
+        TerminalSpec::new("kitty", &[]),
+        TerminalSpec::new("xterm", &["-e"]),
+    ]
+}
+
+impl TerminalConfig {
+    // 端末内で実行する argv に変換する
+    pub fn wrap(&self, argv: Vec<String>, keep_open: bool) -> anyhow::Result<Vec<String>> {
+        let terminal = self
+            .candidates
+            .iter()
+            .find(|t| path_lookup::find_program(&t.program).is_some())
+            .with_context(|| {
+                let names: Vec<&str> = self.candidates.iter().map(|t| t.program.as_str()).collect();
+                format!("利用できる端末が見つかりません: {}", names.join(", "))
+            })?;
+        Ok(wrap_with(terminal, argv, keep_open))
+    }
+}
+
+fn wrap_with(terminal: &TerminalSpec, argv: Vec<String>, keep_open: bool) -> Vec<String> {
+    let argv = if keep_open {
+        keep_open_argv(argv)
+    } else {
+        argv
+    };
+    std::iter::once(terminal.program.clone())
+        .chain(terminal.args.iter().cloned())
+        .chain(argv)
+        .collect()
+}
+
+// 終了後も端末を閉じないようにシェル経由で実行する
+#[cfg(windows)]
+fn keep_open_argv(argv: Vec<String>) -> Vec<String> {
+    ["cmd.exe", "/k"]
+        .into_iter()
+        .map(str::to_string)
+        .chain(argv)
+        .collect()
+}
+
+#[cfg(not(windows))]
+fn keep_open_argv(argv: Vec<String>) -> Vec<String> {
+    const SCRIPT: &str =
+        r#""$@"; status=$?; printf '\n[exit %s] Enter で閉じます' "$status"; read -r _"#;
+    ["sh", "-c", SCRIPT, "sh"]
+        .into_iter()
+        .map(str::to_string)
+        .chain(argv)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_argv_with_terminal_and_keep_open_shell() {
+        let terminal = TerminalSpec::new("xterm", &["-e"]);
+        let argv = vec!["htop".to_string(), "-d".to_string(), "10".to_string()];
+
+        assert_eq!(
+            wrap_with(&terminal, argv.clone(), false),
+            ["xterm", "-e", "htop", "-d", "10"]
+        );
+        let kept = wrap_with(&terminal, argv, true);
+        assert_eq!(kept[..2], ["xterm", "-e"]);
+        assert_eq!(kept[kept.len() - 3..], ["htop", "-d", "10"]);
+    }
+}
@@ -0,0 +1,243 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use log::info;
+
+use crate::app::endpoint::UiEvent;
+use crate::explain::{self, Explanation};
+use crate::model::commands::{CommandSpec, Commands};
+use crate::model::ranking::{self, Usage};
+use crate::runner::{self, LaunchDeps};
+
+// 入力からコマンドを選んで起動を依頼する（画面の描画は Launcher が行う）
+// 重複起動の判定と起動は Controller が行う
+// 変数（`cache: launch`、${clipboard}）の評価は別スレッドで行い、結果を poll で受け取る
+pub struct Dispatcher {
+    commands: Commands,
+    // 候補の並び替えに使う起動履歴
+    usage: Usage,
+    deps: LaunchDeps,
+    tx: Sender<UiEvent>,
+    // 最後に出力をログに記録したコマンドのログファイル
+    last_log: Option<PathBuf>,
+    // 確認画面を表示中のコマンド（resolve 済み）
+    pending_confirm: Option<CommandSpec>,
+    // 変数を評価中のコマンドの数と、評価の結果
+    resolving: usize,
+    resolved_tx: Sender<CommandSpec>,
+    resolved_rx: Receiver<CommandSpec>,
+}
+
+impl Dispatcher {
+    pub fn new(commands: Commands, usage: Usage, deps: LaunchDeps, tx: Sender<UiEvent>) -> Self {
+        let (resolved_tx, resolved_rx) = mpsc::channel();
+        Self {
+            commands,
+            usage,
+            deps,
+            tx,
+            last_log: None,
+            pending_confirm: None,
+            resolving: 0,
+            resolved_tx,
+            resolved_rx,
+        }
+    }
+
+    pub fn last_log(&self) -> Option<&Path> {
+        self.last_log.as_deref()
+    }
+
+    pub fn pending_confirm(&self) -> Option<&CommandSpec> {
+        self.pending_confirm.as_ref()
+    }
+
+    // 変数を評価中のコマンドがあるか（あれば poll を呼び続ける）
+    pub fn is_resolving(&self) -> bool {
+        self.resolving > 0
+    }
+
+    // Controller がコマンドを起動した
+    pub fn launched(&mut self, name: &str, log: Option<PathBuf>) {
+        self.usage.record(name, chrono::Local::now());
+        if log.is_some() {
+            self.last_log = log;
+        }
+    }
+
+    // 入力をコマンド名と query（名前に続けて入力したテキスト）に分ける
+    // 名前に空白を含むコマンドがあるため、入力全体が名前と一致する場合は query なし
+    pub fn split_input<'a>(&self, input: &'a str) -> (&'a str, &'a str) {
+        let input = input.trim();
+        if self.commands.find_by_name(input).is_some() {
+            return (input, "");
+        }
+        match input.split_once(char::is_whitespace) {
+            Some((name, query)) => (name, query.trim_start()),
+            None => (input, ""),
+        }
+    }
+
+    // 入力に一致するコマンドを、よく使う順に並べたもの
+    pub fn candidates(&self, input: &str) -> Vec<&CommandSpec> {
+        let (name, _) = self.split_input(input);
+        ranking::rank(&self.commands, &self.usage, name, chrono::Local::now())
+    }
+
+    // Enter で起動するコマンド
+    // 候補を選択している場合はそのコマンド、なければ入力と名前が完全一致するコマンド
+    // あいまい一致の候補は、選択しない限り起動しない
+    fn target(&self, input: &str, selected: Option<&str>) -> Option<&CommandSpec> {
+        if let Some(selected) = selected {
+            // 実行ファイルが見つからないコマンドは候補から起動しない
+            return self
+                .commands
+                .find_by_name(selected)
+                .filter(|command| command.is_available());
+        }
+        let (name, _) = self.split_input(input);
+        if name.is_empty() {
+            return None;
+        }
+        self.commands.find_by_name(name)
+    }
+
+    // Enter で確定した入力を実行する
+    // selected は候補から選択中のコマンド名
+    pub fn submit(&mut self, input: &str, selected: Option<&str>) {
+        let (name, query) = self.split_input(input);
+        if name.is_empty() && selected.is_none() {
+            info!("空のコマンド名が入力されました");
+            return;
+        }
+        match self
+            .target(input, selected)
+            .map(|command| command.name().to_string())
+        {
+            Some(found) => self.run(&found, query),
+            None => info!("名前が一致するコマンドがありません: {:?}", name),
+        }
+    }
+
+    // Enter で起動するコマンドが、どう起動されるかのプレビュー
+    pub fn explain(&self, input: &str, selected: Option<&str>) -> Option<Explanation> {
+        let (_, query) = self.split_input(input);
+        let command = runner::preview(&self.deps, self.target(input, selected)?, query);
+        Some(explain::explain(&command, &self.deps.terminal))
+    }
+
+    // 変数を別スレッドで評価する（コマンド出力の変数で UI を止めない）
+    pub fn run(&mut self, name: &str, query: &str) {
+        let Some(command) = self.commands.find_by_name(name).cloned() else {
+            return;
+        };
+        let deps = self.deps.clone();
+        let tx = self.resolved_tx.clone();
+        let query = query.to_string();
+        self.resolving += 1;
+        std::thread::spawn(move || {
+            let _ = tx.send(runner::resolve(&deps, &command, &query));
+        });
+    }
+
+    // 変数の評価が終わったコマンドの起動を依頼する（毎フレーム呼ぶ）
+    pub fn poll(&mut self) {
+        while let Ok(command) = self.resolved_rx.try_recv() {
+            self.resolving = self.resolving.saturating_sub(1);
+            if command.needs_confirmation() {
+                // 展開後の引数を確認画面に表示し、実行が選ばれてから起動する
+                // 確認画面の表示中に評価が終わったものは、表示中のものを優先して取りやめる
+                if self.pending_confirm.is_some() {
+                    info!("確認画面の表示中のため{:?}を起動しません", command.name());
+                    continue;
+                }
+                self.pending_confirm = Some(command);
+                continue;
+            }
+            self.launch(command);
+        }
+    }
+
+    // 確認画面で実行が選ばれた
+    pub fn confirm(&mut self) {
+        if let Some(command) = self.pending_confirm.take() {
+            self.launch(command);
+        }
+    }
+
+    // 確認画面でキャンセルが選ばれた
+    pub fn cancel(&mut self) {
+        if let Some(command) = self.pending_confirm.take() {
+            info!("{:?}の実行をキャンセルしました", command.name());
+        }
+    }
+
+    // resolve 済みのコマンドの起動を Controller に依頼する
+    fn launch(&mut self, command: CommandSpec) {
+        info!("{:?}の起動を依頼します", command.name());
+        let _ = self.tx.send(UiEvent::Launch(Box::new(command)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::spawner::RecordingSpawner;
+
+    #[test]
+    fn enter_launches_exact_argv() {
+        let commands = Commands::new(
+            serde_yaml::from_str(
+                r#"
+- { name: "search", program: "firefox", args: ["https://example.com/?q=${query}"] }
+- { name: "deploy", program: "deploy", args: ["--prod"], confirm: true }
+"#,
+            )
+            .unwrap(),
+        );
+        let (tx, rx) = mpsc::channel();
+        let mut dispatcher = Dispatcher::new(
+            commands,
+            Usage::default(),
+            LaunchDeps::recording(RecordingSpawner::default()),
+            tx,
+        );
+        // 変数の評価は別スレッドで行うため、結果を受け取るまで poll する
+        let settle = |dispatcher: &mut Dispatcher| {
+            let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+            while dispatcher.is_resolving() && std::time::Instant::now() < deadline {
+                dispatcher.poll();
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+        };
+        let requested = || -> Vec<Vec<String>> {
+            rx.try_iter()
+                .filter_map(|event| match event {
+                    UiEvent::Launch(command) => Some(runner::resolved_argv(&command)),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // 前方一致するだけの候補は、選択しない限り起動しない
+        dispatcher.submit("sea rust lang", None);
+        assert!(requested().is_empty());
+
+        // 選択した候補を query 付きで起動する
+        dispatcher.submit("sea rust lang", Some("search"));
+        settle(&mut dispatcher);
+        assert_eq!(
+            requested(),
+            [["firefox", "https://example.com/?q=rust lang"]]
+        );
+
+        // 確認が必要なコマンドは、確認するまで起動しない
+        dispatcher.submit("deploy", None);
+        settle(&mut dispatcher);
+        assert!(requested().is_empty());
+        dispatcher.confirm();
+        assert_eq!(requested(), [["deploy", "--prod"]]);
+    }
+}
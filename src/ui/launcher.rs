@@ -6,10 +6,18 @@ use windows_sys::Win32::Foundation::HWND;
 use windows_sys::Win32::System::Threading::GetCurrentThreadId;
 
 use crate::config::Settings;
-use crate::model::commands;
-use crate::runner;
+use crate::history;
+use crate::model::commands::DangerLevel;
+use crate::runner::{self, LaunchDeps};
 
 use crate::app::endpoint::{UiCommand, UiEndpoint, UiEvent};
+use crate::app::pipeline::WaitingLaunch;
+use crate::app::processes::{CommandOutput, ProcessInfo, ProcessState};
+use crate::app::supervisor::{SupervisionState, SupervisionStatus};
+
+use crate::explain::Explanation;
+
+use super::dispatcher::Dispatcher;
 
 // use super::hotkey::HotkeyToggle;
 // use super::task_tray::{TaskTray, TrayCommand};
@@ -22,23 +30,50 @@ enum InitState {
 pub struct Launcher {
     state: InitState,
     command_input: String,
-    commands: commands::Commands,
+    // ↑↓ で選択中の候補の位置（入力を変更すると解除する）
+    selected: Option<usize>,
+    // 入力からのコマンドの起動
+    dispatcher: Dispatcher,
     hwnd: Option<HWND>,
 
     endpoint: UiEndpoint,
     last_app_focused: Option<bool>,
+    // 起動したプロセスの一覧（Controller から受信）
+    processes: Vec<ProcessInfo>,
+    // output: show のコマンドの出力（Controller から受信）
+    output: Option<CommandOutput>,
+    // restart のコマンドの起動し直しの状態（Controller から受信）
+    supervision: Vec<SupervisionStatus>,
+    // 同時実行数の上限に達して待機中の起動（Controller から受信）
+    waiting: Vec<WaitingLaunch>,
+    // F1 で表示する、Enter で起動するコマンドのプレビュー
+    preview: Option<Explanation>,
 }
 
+// 入力欄の下に表示する候補の最大数
+const MAX_CANDIDATES: usize = 10;
+
 impl Launcher {
-    pub fn new(settings: Settings, endpoint: UiEndpoint) -> anyhow::Result<Self> {
-        let commands = settings.commands();
+    pub fn new(settings: Settings, endpoint: UiEndpoint, deps: LaunchDeps) -> anyhow::Result<Self> {
+        let dispatcher = Dispatcher::new(
+            settings.commands(),
+            history::load_usage(),
+            deps,
+            endpoint.tx.clone(),
+        );
         Ok(Self {
             state: InitState::Start,
             command_input: String::new(),
-            commands,
+            selected: None,
+            dispatcher,
             hwnd: None,
             endpoint,
             last_app_focused: None,
+            processes: Vec::new(),
+            output: None,
+            supervision: Vec::new(),
+            waiting: Vec::new(),
+            preview: None,
         })
     }
 
@@ -75,6 +110,21 @@ impl Launcher {
                     // フォーカスを入力欄に移す
                     // UIを作成後に実装する
                 }
+                UiCommand::UpdateProcesses(processes) => {
+                    self.processes = processes;
+                }
+                UiCommand::ShowOutput(output) => {
+                    self.output = Some(output);
+                }
+                UiCommand::UpdateSupervision(statuses) => {
+                    self.supervision = statuses;
+                }
+                UiCommand::Launched { name, log } => {
+                    self.dispatcher.launched(&name, log);
+                }
+                UiCommand::UpdateWaiting(waiting) => {
+                    self.waiting = waiting;
+                }
             }
         }
     }
@@ -94,28 +144,290 @@ impl Launcher {
     }
     // コマンド実行機能
 
+    // 選択中の候補、なければ入力と名前が完全一致するコマンドを起動する
     fn try_run_command(&mut self) {
-        let input = self.command_input.trim();
-        if input.is_empty() {
-            info!("空のコマンド名が入力されました");
+        let selected = self.selected_name();
+        self.dispatcher
+            .submit(&self.command_input, selected.as_deref());
+        if !self.command_input.trim().is_empty() || selected.is_some() {
+            self.command_input.clear();
+        }
+        self.selected = None;
+    }
+
+    fn selected_name(&self) -> Option<String> {
+        let index = self.selected?;
+        self.dispatcher
+            .candidates(&self.command_input)
+            .get(index)
+            .map(|command| command.name().to_string())
+    }
+
+    // ↑↓ で候補を選択する
+    fn move_selection(&mut self, ui: &egui::Ui) {
+        let (up, down) = ui.input(|i| {
+            (
+                i.key_pressed(egui::Key::ArrowUp),
+                i.key_pressed(egui::Key::ArrowDown),
+            )
+        });
+        if !up && !down {
+            return;
+        }
+        let count = self
+            .dispatcher
+            .candidates(&self.command_input)
+            .len()
+            .min(MAX_CANDIDATES);
+        if count == 0 {
+            self.selected = None;
             return;
         }
+        self.selected = match (self.selected, down) {
+            (None, true) => Some(0),
+            (None, false) => None,
+            (Some(i), true) => Some((i + 1).min(count - 1)),
+            (Some(0), false) => None,
+            (Some(i), false) => Some(i - 1),
+        };
+    }
+
+    // 起動前の確認画面
+    fn show_confirm_dialog(&mut self, ctx: &egui::Context) {
+        let Some(command) = self.dispatcher.pending_confirm() else {
+            return;
+        };
+        let mut decision = None;
+        egui::Window::new("実行の確認")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                if command.danger() == DangerLevel::High {
+                    ui.colored_label(
+                        egui::Color32::RED,
+                        "危険なコマンドです。内容を確認してください",
+                    );
+                }
+                ui.label(format!("{} を実行しますか？", command.name()));
+                ui.add(
+                    egui::Label::new(
+                        egui::RichText::new(runner::resolved_argv(command).join(" ")).monospace(),
+                    )
+                    .wrap(),
+                );
+                ui.horizontal(|ui| {
+                    if ui.button("実行").clicked() {
+                        decision = Some(true);
+                    }
+                    if ui.button("キャンセル").clicked() {
+                        decision = Some(false);
+                    }
+                });
+            });
+        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            decision = Some(false);
+        }
+        match decision {
+            Some(true) => self.dispatcher.confirm(),
+            Some(false) => self.dispatcher.cancel(),
+            None => {}
+        }
+    }
 
-        let Some(command) = self.commands.find_by_name(input) else {
-            info!("指定されたコマンドが見つかりません: {:?}", input);
+    // 候補の一覧。クリックで起動する
+    fn show_candidates(&mut self, ui: &mut egui::Ui) {
+        let mut clicked = None;
+        for (i, command) in self
+            .dispatcher
+            .candidates(&self.command_input)
+            .into_iter()
+            .take(MAX_CANDIDATES)
+            .enumerate()
+        {
+            // 実行ファイルが見つからないコマンドは選べないようにし、理由を表示する
+            let response = ui
+                .add_enabled(
+                    command.is_available(),
+                    egui::SelectableLabel::new(self.selected == Some(i), command.name()),
+                )
+                .on_hover_text(command.program_summary())
+                .on_disabled_hover_text(command.program_summary());
+            if response.clicked() {
+                clicked = Some(command.name().to_string());
+            }
+        }
+        if let Some(name) = clicked {
+            let query = self
+                .dispatcher
+                .split_input(&self.command_input)
+                .1
+                .to_string();
+            self.dispatcher.run(&name, &query);
             self.command_input.clear();
+            self.selected = None;
+        }
+    }
+
+    // 起動のプレビュー（explain と同じ内容）
+    fn show_preview(&mut self, ui: &mut egui::Ui) {
+        let Some(preview) = &self.preview else {
             return;
         };
+        let mut close = false;
+        ui.horizontal(|ui| {
+            ui.label(format!("{} のプレビュー", preview.name));
+            if ui.button("閉じる").clicked() {
+                close = true;
+            }
+        });
+        egui::ScrollArea::vertical()
+            .id_salt("preview")
+            .max_height(200.0)
+            .show(ui, |ui| {
+                ui.add(
+                    egui::Label::new(egui::RichText::new(preview.lines().join("\n")).monospace())
+                        .selectable(true),
+                );
+            });
+        if close {
+            self.preview = None;
+        }
+    }
 
-        match runner::spawn_command(command) {
-            Ok(_child) => {
-                info!("{:?}を起動しました", command.name());
-                self.command_input.clear();
+    // output: show のコマンドの出力
+    fn show_output(&mut self, ui: &mut egui::Ui) {
+        let Some(output) = &self.output else {
+            return;
+        };
+        let mut close = false;
+        ui.horizontal(|ui| {
+            let status = match output.exit_code {
+                Some(0) => String::new(),
+                Some(code) => format!(" (終了コード {code})"),
+                None => " (異常終了)".to_string(),
+            };
+            ui.label(format!("{} の出力{}", output.name, status));
+            if ui.button("コピー").clicked() {
+                ui.ctx().copy_text(output.text.clone());
+            }
+            if ui.button("1行目をコピー").clicked() {
+                let first = output.text.lines().next().unwrap_or_default();
+                ui.ctx().copy_text(first.to_string());
             }
-            Err(e) => {
-                info!("起動に失敗しました: {e:?}");
-                self.command_input.clear();
+            if ui.button("閉じる").clicked() {
+                close = true;
             }
+        });
+        if output.truncated {
+            ui.label("出力が上限を超えたため切り詰めました");
+        }
+        egui::ScrollArea::vertical()
+            .max_height(200.0)
+            .show(ui, |ui| {
+                ui.add(
+                    egui::Label::new(egui::RichText::new(&output.text).monospace())
+                        .selectable(true),
+                );
+            });
+        if close {
+            self.output = None;
+        }
+    }
+
+    // 同時実行数の上限に達して待機中のコマンドと順番
+    fn show_waiting(&mut self, ui: &mut egui::Ui) {
+        if self.waiting.is_empty() {
+            return;
+        }
+        let mut cancel = None;
+        egui::CollapsingHeader::new("待機中のコマンド")
+            .default_open(true)
+            .show(ui, |ui| {
+                for (i, waiting) in self.waiting.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{} 番目: {}", i + 1, waiting.name));
+                        if ui.button("取り消し").clicked() {
+                            cancel = Some(waiting.id);
+                        }
+                    });
+                }
+            });
+        if let Some(id) = cancel {
+            let _ = self.endpoint.tx.send(UiEvent::CancelWaiting(id));
+        }
+    }
+
+    // 起動し直しを待っている、または諦めたコマンド
+    fn show_supervision(&self, ui: &mut egui::Ui) {
+        if self.supervision.is_empty() {
+            return;
+        }
+        egui::CollapsingHeader::new("起動し直し")
+            .default_open(true)
+            .show(ui, |ui| {
+                for status in &self.supervision {
+                    match &status.state {
+                        SupervisionState::Waiting { attempt, at } => {
+                            ui.label(format!(
+                                "{} {} に起動し直します ({}/{} 回目)",
+                                status.name,
+                                at.format("%H:%M:%S"),
+                                attempt,
+                                status.max_retries
+                            ));
+                        }
+                        SupervisionState::GaveUp { attempts } => {
+                            ui.colored_label(
+                                egui::Color32::RED,
+                                format!(
+                                    "{} は {} 回起動し直しても終了したため、起動し直しをやめました",
+                                    status.name, attempts
+                                ),
+                            );
+                        }
+                        SupervisionState::Idle => {}
+                    }
+                }
+            });
+    }
+
+    // 起動したプロセスの一覧と終了操作
+    fn show_processes(&mut self, ui: &mut egui::Ui) {
+        if self.processes.is_empty() {
+            return;
+        }
+        let mut requests = Vec::new();
+        egui::CollapsingHeader::new("起動したプロセス")
+            .default_open(true)
+            .show(ui, |ui| {
+                for process in self.processes.iter().rev() {
+                    ui.horizontal(|ui| {
+                        let state = match process.state {
+                            ProcessState::Running => "実行中".to_string(),
+                            ProcessState::Exited(Some(code)) => format!("終了 ({code})"),
+                            ProcessState::Exited(None) => "終了".to_string(),
+                        };
+                        ui.label(format!(
+                            "{} pid={} {} {}",
+                            process.name,
+                            process.pid,
+                            process.started_at.format("%H:%M:%S"),
+                            state
+                        ));
+                        if process.is_running() {
+                            if ui.button("終了").clicked() {
+                                requests.push(UiEvent::TerminateProcess(process.pid));
+                            }
+                            if ui.button("強制終了").clicked() {
+                                requests.push(UiEvent::KillProcess(process.pid));
+                            }
+                        }
+                    });
+                }
+            });
+        for request in requests {
+            let _ = self.endpoint.tx.send(request);
         }
     }
 }
@@ -133,9 +445,14 @@ impl eframe::App for Launcher {
         // コントローラーからのイベント処理
         self.process_controller(ctx, frame);
 
+        // 変数の評価が終わったコマンドの起動
+        self.dispatcher.poll();
+
         // 非アクティブ化時の処理
         self.process_focus_lost(ctx);
 
+        self.show_confirm_dialog(ctx);
+
         // メインUI
         egui::CentralPanel::default().show(ctx, |ui| {
             let response = ui.add(
@@ -143,6 +460,10 @@ impl eframe::App for Launcher {
                     .hint_text("コマンド名を入力して Enter で実行")
                     .desired_width(f32::INFINITY),
             );
+            if response.changed() {
+                self.selected = None;
+            }
+            self.move_selection(ui);
 
             // Enter キー（英字入力）または IME の確定で実行されるようにする
             let enter_pressed = ui.input(|i| i.key_pressed(egui::Key::Enter));
@@ -154,8 +475,37 @@ impl eframe::App for Launcher {
                 self.try_run_command();
             }
 
+            // F1 で Enter で起動するコマンドのプレビューを表示する
+            if ui.input(|i| i.key_pressed(egui::Key::F1)) {
+                let selected = self.selected_name();
+                self.preview = self
+                    .dispatcher
+                    .explain(&self.command_input, selected.as_deref());
+            }
+
+            self.show_candidates(ui);
+
             ui.separator();
             // ui.label(&self.status);
+            if let Some(path) = self.dispatcher.last_log() {
+                ui.label(format!("ログ: {}", path.display()));
+            }
+
+            self.show_preview(ui);
+            self.show_output(ui);
+            self.show_waiting(ui);
+            self.show_supervision(ui);
+            self.show_processes(ui);
         });
+
+        // 変数を評価中のコマンド、実行中のプロセスや待機中の起動がある間は更新し続ける
+        // （起動は Controller が行うため、変数の評価の結果の受け取り以外は表示の更新だけ）
+        if self.dispatcher.is_resolving() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(50));
+        } else if !self.waiting.is_empty() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(500));
+        } else if self.processes.iter().any(ProcessInfo::is_running) {
+            ctx.request_repaint_after(std::time::Duration::from_secs(1));
+        }
     }
 }
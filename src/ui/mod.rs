@@ -1,3 +1,4 @@
+mod dispatcher;
 mod launcher;
 mod native_runner;
 
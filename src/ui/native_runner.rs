@@ -2,13 +2,18 @@ use eframe::egui;
 use log::info;
 
 use crate::config::Settings;
+use crate::runner::LaunchDeps;
 use crate::ui::Launcher;
 
 use crate::app::endpoint::UiEndpoint;
 
 const FONT: &[u8] = include_bytes!(r"C:/Windows/Fonts/MEIRYO.TTC");
 
-pub fn eframe_startup(settings: Settings, ui_endpoint: UiEndpoint) -> anyhow::Result<()> {
+pub fn eframe_startup(
+    settings: Settings,
+    ui_endpoint: UiEndpoint,
+    deps: LaunchDeps,
+) -> anyhow::Result<()> {
     let native_options = eframe::NativeOptions::default();
 
     eframe::run_native(
@@ -17,7 +22,7 @@ pub fn eframe_startup(settings: Settings, ui_endpoint: UiEndpoint) -> anyhow::Re
         Box::new(move |cc| {
             initialize(&cc.egui_ctx);
 
-            Ok(Box::new(Launcher::new(settings, ui_endpoint)?))
+            Ok(Box::new(Launcher::new(settings, ui_endpoint, deps)?))
         }),
     )
     .map_err(|e| anyhow::Error::msg(format!("UI を起動できません: {e:?}")))?;